@@ -0,0 +1,237 @@
+//! Benchmarking the built executable with repeated runs.
+//!
+//! Results are appended to `<build-dir>/bench/<target>.jsonl`, and compared to the previous
+//! results with the same arguments.
+//!
+//! ```yaml
+//! bench:
+//!   warmup: 2      # runs before measuring
+//!   threshold: 5   # slowdown in percent flagged as a regression
+//! ```
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use config::Config;
+use serde_json::json;
+
+use crate::format_size;
+
+/// Default slowdown in percent flagged as a regression.
+const DEFAULT_THRESHOLD: f64 = 5.0;
+
+/// Measurement of a single run.
+#[derive(Clone, Copy, Debug)]
+pub struct Sample {
+    pub wall: Duration,
+
+    /// Peak resident set size in bytes
+    pub max_rss: u64,
+}
+
+/// Summary statistics of a series of values.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Stats {
+    pub mean: f64,
+    pub median: f64,
+
+    /// Sample standard deviation
+    pub stddev: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Stats {
+    /// Compute the statistics, all zeros for an empty series.
+    ///
+    /// ```
+    /// use baldr::bench::Stats;
+    ///
+    /// let stats = Stats::new(&[1.0, 3.0, 2.0, 6.0]);
+    ///
+    /// assert_eq!(stats.mean, 3.0);
+    /// assert_eq!(stats.median, 2.5);
+    /// assert_eq!(stats.min, 1.0);
+    /// assert_eq!(stats.max, 6.0);
+    /// ```
+    pub fn new(values: &[f64]) -> Self {
+        if values.is_empty() {
+            return Self::default();
+        }
+
+        let mut sorted = values.to_vec();
+        sorted.sort_by(f64::total_cmp);
+
+        let n = sorted.len();
+        let mean = sorted.iter().sum::<f64>() / n as f64;
+        let median = if n % 2 == 0 {
+            (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0
+        } else {
+            sorted[n / 2]
+        };
+
+        let stddev = if n > 1 {
+            (sorted.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1) as f64).sqrt()
+        } else {
+            0.0
+        };
+
+        Self { mean, median, stddev, min: sorted[0], max: sorted[n - 1] }
+    }
+}
+
+/// Warmup runs before measuring.
+pub fn get_warmup(cfg: &Config) -> u32 {
+    cfg.get_int("bench.warmup").ok()
+        .and_then(|x| u32::try_from(x).ok())
+        .unwrap_or(1)
+}
+
+/// Format a duration given in seconds, e.g. `12.345ms`.
+pub fn format_duration(secs: f64) -> String {
+    if secs < 1e-3 {
+        format!("{:.1}µs", secs * 1e6)
+    } else if secs < 1.0 {
+        format!("{:.3}ms", secs * 1e3)
+    } else {
+        format!("{secs:.3}s")
+    }
+}
+
+fn history_file(build_dir: &Path, target: &str) -> PathBuf {
+    build_dir.join("bench").join(format!("{}.jsonl", target.replace('/', "_")))
+}
+
+/// Mean wall time and peak RSS of the last results with the same arguments.
+fn previous(file: &Path, exe_args: &[String]) -> Option<(f64, f64)> {
+    let content = fs::read_to_string(file).ok()?;
+
+    content.lines()
+        .rev()
+        .filter_map(|x| serde_json::from_str::<serde_json::Value>(x).ok())
+        .find(|x| x["args"] == json!(exe_args))
+        .and_then(|x| Some((x["wall"]["mean"].as_f64()?, x["max_rss"]["mean"].as_f64()?)))
+}
+
+/// Relative change in percent.
+fn change(previous: f64, current: f64) -> f64 {
+    if previous == 0.0 {
+        return 0.0;
+    }
+    (current - previous) / previous * 100.0
+}
+
+/// Print the statistics of the samples, compare them to the previous results and save them.
+///
+/// # Errors
+///
+/// Returns an error if the results cannot be saved.
+pub fn report(build_dir: &Path, target: &str, exe_args: &[String], samples: &[Sample], cfg: &Config) -> Result<(), String> {
+    let wall = Stats::new(&samples.iter().map(|x| x.wall.as_secs_f64()).collect::<Vec<_>>());
+    let rss = Stats::new(&samples.iter().map(|x| x.max_rss as f64).collect::<Vec<_>>());
+
+    let size = |x: f64| format_size(x as u64);
+
+    println!("{:<10}  {:>10}  {:>10}  {:>10}  {:>10}  {:>10}", "", "MEAN", "MEDIAN", "STDDEV", "MIN", "MAX");
+    println!(
+        "{:<10}  {:>10}  {:>10}  {:>10}  {:>10}  {:>10}",
+        "wall time",
+        format_duration(wall.mean),
+        format_duration(wall.median),
+        format_duration(wall.stddev),
+        format_duration(wall.min),
+        format_duration(wall.max),
+    );
+    println!(
+        "{:<10}  {:>10}  {:>10}  {:>10}  {:>10}  {:>10}",
+        "peak RSS",
+        size(rss.mean),
+        size(rss.median),
+        size(rss.stddev),
+        size(rss.min),
+        size(rss.max),
+    );
+
+    let file = history_file(build_dir, target);
+    let threshold = cfg.get_float("bench.threshold").unwrap_or(DEFAULT_THRESHOLD);
+
+    if let Some((prev_wall, prev_rss)) = previous(&file, exe_args) {
+        for (name, prev, current) in [("wall time", prev_wall, wall.mean), ("peak RSS", prev_rss, rss.mean)] {
+            let change = change(prev, current);
+            let flag = if change > threshold { "  REGRESSION" } else { "" };
+            println!("{name}: {change:+.1}% compared to the previous run{flag}");
+        }
+    }
+
+    let stats = |x: Stats| json!({
+        "mean": x.mean,
+        "median": x.median,
+        "stddev": x.stddev,
+        "min": x.min,
+        "max": x.max,
+    });
+
+    let record = json!({
+        "timestamp": SystemTime::now().duration_since(UNIX_EPOCH).map(|x| x.as_secs()).unwrap_or_default(),
+        "args": exe_args,
+        "runs": samples.len(),
+        "wall": stats(wall),
+        "max_rss": stats(rss),
+    });
+
+    if let Some(dir) = file.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create `{}`: {e}", dir.display()))?;
+    }
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&file)
+        .and_then(|mut f| writeln!(f, "{record}"))
+        .map_err(|e| format!("Failed to save benchmark results into `{}`: {e}", file.display()))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats() {
+        let stats = Stats::new(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]);
+        assert_eq!(stats.mean, 5.0);
+        assert_eq!(stats.median, 4.5);
+        assert!((stats.stddev - 2.138).abs() < 1e-3);
+
+        assert_eq!(Stats::new(&[1.5]).stddev, 0.0);
+        assert_eq!(Stats::new(&[]), Stats::default());
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(format_duration(0.000_012), "12.0µs");
+        assert_eq!(format_duration(0.012_345_6), "12.346ms");
+        assert_eq!(format_duration(2.5), "2.500s");
+    }
+
+    #[test]
+    fn history() {
+        let dir = std::env::temp_dir().join(format!("baldr-bench-{}", std::process::id()));
+        let samples = [Sample { wall: Duration::from_millis(10), max_rss: 1024 }];
+        let args = vec!["x".to_string()];
+
+        report(&dir, "app", &args, &samples, &Config::default()).unwrap();
+        report(&dir, "app", &[], &samples, &Config::default()).unwrap();
+
+        let (wall, rss) = previous(&history_file(&dir, "app"), &args).unwrap();
+        assert!((wall - 0.01).abs() < 1e-9);
+        assert_eq!(rss, 1024.0);
+        assert!(previous(&history_file(&dir, "app"), &["y".to_string()]).is_none());
+
+        fs::remove_dir_all(dir).unwrap();
+        assert!((change(10.0, 11.0) - 10.0).abs() < 1e-9);
+    }
+}
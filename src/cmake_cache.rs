@@ -0,0 +1,158 @@
+//! Reading and inspecting CMake's cache (`CMakeCache.txt`) of a build directory.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// A variable in the cache, e.g. `CMAKE_BUILD_TYPE:STRING=Debug`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub name: String,
+    pub kind: String,
+    pub value: String,
+
+    /// Help string (the `//` comment lines preceding the entry)
+    pub help: String,
+}
+
+impl fmt::Display for Entry {
+    /// Format the entry as in the cache, preceded by its help string.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in self.help.lines() {
+            writeln!(f, "//{line}")?;
+        }
+        write!(f, "{}:{}={}", self.name, self.kind, self.value)
+    }
+}
+
+/// Parse the content of a CMake cache.
+///
+/// ```
+/// use baldr::cmake_cache::parse;
+///
+/// let entries = parse("//Choose the type of build.\nCMAKE_BUILD_TYPE:STRING=Debug\n");
+///
+/// assert_eq!(entries[0].name, "CMAKE_BUILD_TYPE");
+/// assert_eq!(entries[0].kind, "STRING");
+/// assert_eq!(entries[0].value, "Debug");
+/// assert_eq!(entries[0].help, "Choose the type of build.");
+/// ```
+pub fn parse(content: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut help: Vec<&str> = Vec::new();
+
+    for line in content.lines() {
+        if let Some(comment) = line.strip_prefix("//") {
+            help.push(comment);
+            continue;
+        }
+
+        if line.is_empty() || line.starts_with('#') {
+            help.clear();
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            help.clear();
+            continue;
+        };
+
+        // Names containing a colon are quoted.
+        let key = key.trim_matches('"');
+        let (name, kind) = key.rsplit_once(':').unwrap_or((key, "UNINITIALIZED"));
+
+        entries.push(Entry {
+            name: name.trim_matches('"').to_string(),
+            kind: kind.to_string(),
+            value: value.to_string(),
+            help: help.join("\n"),
+        });
+
+        help.clear();
+    }
+
+    entries
+}
+
+/// Read the cache of a build directory.
+///
+/// # Errors
+///
+/// Returns an error if the build directory has no cache or it cannot be read.
+pub fn read(build_dir: &Path) -> Result<Vec<Entry>, String> {
+    let file = build_dir.join("CMakeCache.txt");
+    let content = fs::read_to_string(&file)
+        .map_err(|e| format!("Failed to read `{}`: {e}", file.display()))?;
+
+    Ok(parse(&content))
+}
+
+/// Value of a variable in the cache.
+pub fn get<'a>(entries: &'a [Entry], name: &str) -> Option<&'a str> {
+    entries.iter()
+        .find(|x| x.name == name)
+        .map(|x| x.value.as_str())
+}
+
+/// Find a variable in the cache.
+pub fn find<'a>(entries: &'a [Entry], name: &str) -> Option<&'a Entry> {
+    entries.iter().find(|x| x.name == name)
+}
+
+/// Whether the variable is internal to CMake, i.e. it is not meant to be edited.
+pub fn is_internal(entry: &Entry) -> bool {
+    entry.kind == "INTERNAL" || entry.kind == "STATIC"
+}
+
+/// Print a variable of the build directory's cache.
+///
+/// # Errors
+///
+/// Returns an error if the cache cannot be read or the variable is not in it.
+pub fn show(build_dir: &Path, name: &str) -> Result<(), String> {
+    let entries = read(build_dir)?;
+    let entry = find(&entries, name)
+        .ok_or_else(|| format!("`{name}` is not in the cache of `{}`", build_dir.display()))?;
+
+    println!("{entry}");
+    Ok(())
+}
+
+/// Print the variables of the build directory's cache, optionally only the ones whose name
+/// contains the filter.
+///
+/// Internal variables are only listed if `all` is set.
+///
+/// # Errors
+///
+/// Returns an error if the cache cannot be read.
+pub fn list(build_dir: &Path, filter: Option<&str>, all: bool) -> Result<(), String> {
+    let filter = filter.map(str::to_lowercase);
+    let mut entries = read(build_dir)?;
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let selected = entries.iter()
+        .filter(|x| all || !is_internal(x))
+        .filter(|x| filter.as_ref().map_or(true, |f| x.name.to_lowercase().contains(f)));
+
+    for entry in selected {
+        println!("{entry}\n");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        let content = "# comment\n//Build the tests.\n//Off by default.\nBUILD_TESTS:BOOL=OFF\n\nCMAKE_COMMAND:INTERNAL=/usr/bin/cmake\n";
+        let entries = parse(content);
+
+        assert_eq!(entries[0].to_string(), "//Build the tests.\n//Off by default.\nBUILD_TESTS:BOOL=OFF");
+        assert!(!is_internal(&entries[0]));
+        assert!(is_internal(find(&entries, "CMAKE_COMMAND").unwrap()));
+    }
+}
@@ -0,0 +1,428 @@
+//! Code coverage builds and reports.
+//!
+//! Coverage builds use a dedicated build directory with flags for the family of the compiler:
+//! gcov-style instrumentation for GCC, and source-based coverage for Clang.
+//!
+//! ```yaml
+//! coverage:
+//!   # gcovr or lcov for GCC, llvm-cov for Clang
+//!   tool: gcovr
+//!   # paths relative to the project, `*` and `?` wildcards
+//!   include:
+//!     - src/*
+//!   exclude:
+//!     - src/generated/*
+//! ```
+//!
+//! Reports are written into `<build-dir>/coverage`: HTML into `html/` and lcov into `coverage.info`.
+
+use std::fs::{self, File};
+use std::path::{self, Path};
+use std::process::Command;
+
+use config::Config;
+use log::*;
+
+use crate::{
+    Args,
+    dirs,
+    execute,
+    definition_name,
+    find_executable,
+    find_files,
+    find_in_path,
+    format_cmd,
+    formatting,
+    get_cxx,
+    get_list,
+};
+
+/// Family of the compiler, determining the kind of instrumentation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Family {
+    Gcc,
+    Clang,
+}
+
+impl Family {
+    /// Detect the family from the name of the compiler, e.g. `clang++-18` is Clang.
+    ///
+    /// The default compiler is assumed to be GCC.
+    pub fn detect(cxx: &str) -> Self {
+        let name = Path::new(cxx).file_name().map(|x| x.to_string_lossy()).unwrap_or_default();
+        if name.contains("clang") {
+            Self::Clang
+        } else {
+            Self::Gcc
+        }
+    }
+
+    /// Flags for compiling and linking.
+    pub fn flags(self) -> &'static str {
+        match self {
+            Self::Gcc => "--coverage",
+            Self::Clang => "-fprofile-instr-generate -fcoverage-mapping",
+        }
+    }
+
+    /// Extension of the files the instrumented programs write the coverage data into.
+    fn data_extension(self) -> &'static str {
+        match self {
+            Self::Gcc => ".gcda",
+            Self::Clang => ".profraw",
+        }
+    }
+}
+
+/// Tools producing the reports.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Tool {
+    Gcovr,
+    Lcov,
+    LlvmCov,
+}
+
+/// Get the reporting tool from the configuration, or the default one for the compiler family.
+///
+/// For GCC `gcovr` is preferred if it is installed, `lcov` is used otherwise.
+///
+/// # Errors
+///
+/// Returns an error if the tool is unknown or it does not support the compiler family.
+pub fn get_tool(cfg: &Config, family: Family) -> Result<Tool, String> {
+    let tool = match cfg.get_string("coverage.tool") {
+        Ok(x) => match x.as_str() {
+            "gcovr" => Tool::Gcovr,
+            "lcov" => Tool::Lcov,
+            "llvm-cov" => Tool::LlvmCov,
+            _ => return Err(format!("Unsupported coverage tool `{x}`, use `gcovr`, `lcov` or `llvm-cov`")),
+        },
+        Err(_) => match family {
+            Family::Clang => Tool::LlvmCov,
+            Family::Gcc if find_in_path("gcovr").is_some() => Tool::Gcovr,
+            Family::Gcc => Tool::Lcov,
+        },
+    };
+
+    match (family, tool) {
+        (Family::Gcc, Tool::Gcovr | Tool::Lcov) | (Family::Clang, Tool::LlvmCov) => Ok(tool),
+        _ => Err(format!("Coverage tool `{tool:?}` does not support the {family:?} compiler family")),
+    }
+}
+
+/// Variables of CMake the instrumentation flags are added to.
+const FLAG_VARIABLES: [&str; 4] = [
+    "CMAKE_C_FLAGS",
+    "CMAKE_CXX_FLAGS",
+    "CMAKE_EXE_LINKER_FLAGS",
+    "CMAKE_SHARED_LINKER_FLAGS",
+];
+
+/// Add the instrumentation flags to the CMake definitions of a coverage build.
+///
+/// Flags already defined (e.g. by the user) are extended, so that neither of them is lost.
+///
+/// ```
+/// use config::Config;
+/// use baldr::coverage::add_definitions;
+///
+/// let mut definitions = vec!["CMAKE_CXX_FLAGS=-Wall".to_string()];
+/// add_definitions(&mut definitions, &Config::default());
+///
+/// assert_eq!(definitions[0], "CMAKE_CXX_FLAGS=-Wall --coverage");
+/// assert_eq!(definitions[1], "CMAKE_C_FLAGS=--coverage");
+/// ```
+pub fn add_definitions(definitions: &mut Vec<String>, cfg: &Config) {
+    let flags = Family::detect(&get_cxx(cfg)).flags();
+
+    for variable in FLAG_VARIABLES {
+        let mut defined = false;
+
+        for definition in definitions.iter_mut().filter(|x| definition_name(x) == variable) {
+            definition.push(' ');
+            definition.push_str(flags);
+            defined = true;
+        }
+
+        if !defined {
+            definitions.push(format!("{variable}={flags}"));
+        }
+    }
+}
+
+/// Convert a wildcard pattern relative to the project into an anchored regular expression
+/// matching absolute paths.
+///
+/// ```
+/// use std::path::{self, Path};
+/// use baldr::coverage::to_regex;
+///
+/// assert_eq!(to_regex(Path::new("/home/x/my.project"), "src/*"), r"^/home/x/my\.project/src/.*$");
+/// ```
+pub fn to_regex(project: &Path, pattern: &str) -> String {
+    let escape = |c: char| match c {
+        '*' => ".*".to_string(),
+        '?' => ".".to_string(),
+        c if "\\.+()[]{}|^$".contains(c) => format!("\\{c}"),
+        c => c.to_string(),
+    };
+
+    let project = project.to_string_lossy().chars().map(escape).collect::<String>();
+    let pattern = pattern.chars().map(escape).collect::<String>();
+    format!("^{project}/{pattern}$")
+}
+
+fn data_files(build_dir: &Path, family: Family) -> Vec<String> {
+    find_files(&build_dir.to_path_buf(), |x| x.ends_with(family.data_extension()))
+}
+
+/// Prepare running an instrumented program: remove the coverage data of previous runs, and
+/// direct the profiles of Clang into the build directory.
+///
+/// # Errors
+///
+/// Returns an error if the data of previous runs cannot be removed.
+pub fn prepare(cmd: &mut Command, build_dir: &Path, cfg: &Config, dry_run: bool) -> Result<(), String> {
+    let family = Family::detect(&get_cxx(cfg));
+
+    if dry_run {
+        println!("find {} -name '*{}' -delete", build_dir.display(), family.data_extension());
+    } else {
+        for file in data_files(build_dir, family) {
+            fs::remove_file(&file).map_err(|e| format!("Failed to remove coverage data `{file}`: {e}"))?;
+        }
+    }
+
+    if family == Family::Clang {
+        // The executable might run in another working directory, see `run.cwd`.
+        let build_dir = path::absolute(build_dir).map_err(|e| format!("Invalid build directory: {e}"))?;
+        cmd.env("LLVM_PROFILE_FILE", build_dir.join("coverage").join("profraw").join("%m-%p.profraw"));
+    }
+
+    Ok(())
+}
+
+/// Run a command with its output written into a file.
+fn execute_to_file(cmd: &mut Command, file: &Path, dry_run: bool) -> Result<(), String> {
+    if dry_run {
+        println!("{} > {}", format_cmd(cmd), file.display());
+        return Ok(());
+    }
+
+    let output = File::create(file).map_err(|e| format!("Failed to create `{}`: {e}", file.display()))?;
+    cmd.stdout(output);
+    check(cmd, false)
+}
+
+fn check(cmd: &mut Command, dry_run: bool) -> Result<(), String> {
+    let status = execute(cmd, dry_run)?;
+    if !status.success() {
+        return Err(format!("Command `{}` has failed with {status}", format_cmd(cmd)));
+    }
+    Ok(())
+}
+
+fn gcovr(project: &Path, build_dir: &Path, out: &Path, includes: &[String], excludes: &[String], dry_run: bool) -> Result<(), String> {
+    let mut cmd = Command::new("gcovr");
+    cmd.arg("--root").arg(project);
+    cmd.arg("--object-directory").arg(build_dir);
+
+    for x in includes {
+        cmd.arg("--filter").arg(to_regex(project, x));
+    }
+    for x in excludes {
+        cmd.arg("--exclude").arg(to_regex(project, x));
+    }
+
+    cmd.arg("--txt");
+    cmd.arg("--html-details").arg(out.join("html").join("index.html"));
+    cmd.arg("--lcov").arg(out.join("coverage.info"));
+
+    check(&mut cmd, dry_run)
+}
+
+fn lcov(project: &Path, build_dir: &Path, out: &Path, includes: &[String], excludes: &[String], dry_run: bool) -> Result<(), String> {
+    let info = out.join("coverage.info");
+    let absolute = |x: &String| project.join(x);
+
+    check(
+        Command::new("lcov")
+            .arg("--capture")
+            .arg("--no-external")
+            .arg("--directory").arg(build_dir)
+            .arg("--base-directory").arg(project)
+            .arg("--output-file").arg(&info),
+        dry_run,
+    )?;
+
+    if !includes.is_empty() {
+        check(
+            Command::new("lcov")
+                .arg("--extract").arg(&info)
+                .args(includes.iter().map(absolute))
+                .arg("--output-file").arg(&info),
+            dry_run,
+        )?;
+    }
+
+    check(
+        Command::new("lcov")
+            .arg("--remove").arg(&info)
+            .args(excludes.iter().map(absolute))
+            .arg("--output-file").arg(&info),
+        dry_run,
+    )?;
+
+    check(Command::new("genhtml").arg(&info).arg("--output-directory").arg(out.join("html")), dry_run)?;
+    check(Command::new("lcov").arg("--summary").arg(&info), dry_run)
+}
+
+fn llvm_cov(project: &Path, build_dir: &Path, out: &Path, exe: &Path, includes: &[String], excludes: &[String], dry_run: bool) -> Result<(), String> {
+    if !dry_run && !exe.is_file() {
+        return Err(format!(
+            "Executable `{}` is not found, `llvm-cov` requires the instrumented executable given by `--target`",
+            exe.display()
+        ));
+    }
+
+    let profdata = out.join("coverage.profdata");
+    let mut profiles = data_files(build_dir, Family::Clang);
+    if dry_run {
+        profiles.push(out.join("profraw").join("*.profraw").display().to_string());
+    }
+
+    check(
+        Command::new("llvm-profdata")
+            .args(["merge", "-sparse"])
+            .args(&profiles)
+            .arg("-o").arg(&profdata),
+        dry_run,
+    )?;
+
+    // Includes are given as the list of sources to report on, as llvm-cov can only ignore files.
+    let sources = if includes.is_empty() {
+        vec![]
+    } else {
+        formatting::sources(project, &[])
+            .into_iter()
+            .filter(|x| {
+                let relative = x.strip_prefix(project).unwrap_or(x).to_string_lossy();
+                includes.iter().any(|p| dirs::matches(p, &relative))
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let llvm_cov = |subcommand: &str| {
+        let mut cmd = Command::new("llvm-cov");
+        cmd.arg(subcommand).arg(exe);
+        cmd.arg(format!("-instr-profile={}", profdata.display()));
+        for x in excludes {
+            cmd.arg(format!("-ignore-filename-regex={}", to_regex(project, x)));
+        }
+        cmd
+    };
+
+    check(llvm_cov("report").args(&sources), dry_run)?;
+    check(
+        llvm_cov("show")
+            .arg("-format=html")
+            .arg(format!("-output-dir={}", out.join("html").display()))
+            .args(&sources),
+        dry_run,
+    )?;
+    execute_to_file(llvm_cov("export").arg("-format=lcov").args(&sources), &out.join("coverage.info"), dry_run)
+}
+
+/// Gather the coverage data of the runs and produce the reports.
+///
+/// The text summary is printed to `stdout`, HTML and lcov reports are written into
+/// `<build-dir>/coverage`.
+///
+/// # Errors
+///
+/// Returns an error if there is no coverage data, the tool is not supported or it has failed.
+pub fn report(build_dir: &Path, args: &Args, cfg: &Config) -> Result<(), String> {
+    let project = fs::canonicalize(args.project())
+        .map_err(|e| format!("Invalid project directory `{}`: {e}", args.project()))?;
+
+    let family = Family::detect(&get_cxx(cfg));
+    let tool = get_tool(cfg, family)?;
+
+    if !args.dry_run && data_files(build_dir, family).is_empty() {
+        return Err(format!(
+            "No coverage data is found in `{}`, the program or the tests need to be run first",
+            build_dir.display()
+        ));
+    }
+
+    let includes = get_list(cfg, "coverage.include");
+    let mut excludes = vec!["build/*".to_string()];
+    excludes.extend(get_list(cfg, "coverage.exclude"));
+
+    let out = build_dir.join("coverage");
+    if args.dry_run {
+        println!("mkdir -p {}", out.join("html").display());
+    } else {
+        fs::create_dir_all(out.join("html")).map_err(|e| format!("Failed to create `{}`: {e}", out.display()))?;
+    }
+
+    info!("Producing coverage report with {tool:?}...");
+
+    match tool {
+        Tool::Gcovr => gcovr(&project, build_dir, &out, &includes, &excludes, args.dry_run)?,
+        Tool::Lcov => lcov(&project, build_dir, &out, &includes, &excludes, args.dry_run)?,
+        Tool::LlvmCov => {
+            let exe = find_executable(build_dir, &args.target, args.dry_run)?;
+            llvm_cov(&project, build_dir, &out, &exe, &includes, &excludes, args.dry_run)?;
+        },
+    }
+
+    if !args.dry_run {
+        println!("Coverage report: {}", out.join("html").join("index.html").display());
+        println!("Coverage lcov: {}", out.join("coverage.info").display());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn families() {
+        assert_eq!(Family::detect("/usr/bin/clang++-18"), Family::Clang);
+        assert_eq!(Family::detect("g++"), Family::Gcc);
+        assert_eq!(Family::detect(""), Family::Gcc);
+    }
+
+    #[test]
+    fn tools() {
+        let cfg = Config::builder()
+            .set_override("coverage.tool", "llvm-cov").unwrap()
+            .build().unwrap();
+
+        assert_eq!(get_tool(&cfg, Family::Clang), Ok(Tool::LlvmCov));
+        assert!(get_tool(&cfg, Family::Gcc).is_err());
+        assert_eq!(get_tool(&Config::default(), Family::Clang), Ok(Tool::LlvmCov));
+    }
+
+    #[test]
+    fn profile_file() {
+        let cfg = Config::builder().set_override("compiler.cxx", "clang++").unwrap().build().unwrap();
+
+        let mut cmd = Command::new("true");
+        prepare(&mut cmd, Path::new("build/debug-coverage"), &cfg, true).unwrap();
+
+        let (_, file) = cmd.get_envs().find(|(k, _)| *k == "LLVM_PROFILE_FILE").unwrap();
+        let file = Path::new(file.unwrap());
+        assert!(file.is_absolute());
+        assert!(file.ends_with("build/debug-coverage/coverage/profraw/%m-%p.profraw"));
+    }
+
+    #[test]
+    fn regex() {
+        assert_eq!(to_regex(Path::new("/p"), "build/*"), "^/p/build/.*$");
+        assert_eq!(to_regex(Path::new("/p"), "src/v?.h"), r"^/p/src/v.\.h$");
+    }
+}
@@ -0,0 +1,325 @@
+//! Package manager integration.
+//!
+//! Dependencies are installed into the build directory before configuring, and the generated
+//! toolchain file is handed over to CMake.
+//! The package manager runs in the foreground process group, so it can prompt for credentials.
+//!
+//! ```yaml
+//! dependencies:
+//!   manager: conan      # or vcpkg
+//!   profile: default    # Conan profile (optional)
+//!   vcpkg_root: /opt/vcpkg  # defaults to `VCPKG_ROOT`
+//! ```
+
+use std::env;
+use std::fs;
+use std::path::{self, Path, PathBuf};
+use std::process::Command;
+
+use config::Config;
+use log::*;
+
+use crate::{
+    Args,
+    coverage::Family,
+    execute_interactive,
+    fingerprint,
+    get_cc,
+    get_cxx,
+    get_toolchain,
+};
+
+/// File in the build directory storing the fingerprint of the last successful install.
+const STAMP: &str = ".baldr-deps";
+
+#[derive(Debug, PartialEq)]
+pub enum Manager {
+    Conan,
+    Vcpkg,
+}
+
+/// Read the configured package manager.
+///
+/// # Errors
+///
+/// Returns an error if the package manager is not supported.
+pub fn get_manager(cfg: &Config) -> Result<Option<Manager>, String> {
+    match cfg.get_string("dependencies.manager").ok().as_deref() {
+        None => Ok(None),
+        Some("conan") => Ok(Some(Manager::Conan)),
+        Some("vcpkg") => Ok(Some(Manager::Vcpkg)),
+        Some(x) => Err(format!("Unsupported package manager: `{x}`!")),
+    }
+}
+
+/// Files describing the dependencies, the first existing one is fingerprinted.
+fn lockfiles(manager: &Manager) -> &'static [&'static str] {
+    match manager {
+        Manager::Conan => &["conan.lock", "conanfile.py", "conanfile.txt"],
+        Manager::Vcpkg => &["vcpkg.json"],
+    }
+}
+
+/// Fingerprint of the lockfile (or manifest) of the dependencies, if a package manager is
+/// configured and the file exists.
+pub fn manifest_fingerprint(args: &Args, cfg: &Config) -> Option<String> {
+    let manager = get_manager(cfg).ok()??;
+
+    lockfiles(&manager).iter()
+        .find_map(|x| fs::read(Path::new(args.project()).join(x)).ok())
+        .map(|x| fingerprint(&x))
+}
+
+fn vcpkg_root(cfg: &Config) -> Result<PathBuf, String> {
+    cfg.get_string("dependencies.vcpkg_root")
+        .or_else(|_| env::var("VCPKG_ROOT"))
+        .map(PathBuf::from)
+        .map_err(|_| "vcpkg root is neither configured nor set in `VCPKG_ROOT`".to_string())
+}
+
+fn set_compilers(cmd: &mut Command, cfg: &Config) {
+    let cc = get_cc(cfg);
+    let cxx = get_cxx(cfg);
+    if !cc.is_empty() && !cxx.is_empty() {
+        cmd.env("CC", cc);
+        cmd.env("CXX", cxx);
+    }
+}
+
+/// Conan settings of the compiler, i.e. its name and major version.
+///
+/// The version is asked from the compiler, it is left to the profile if that fails.
+fn conan_compiler_settings(cxx: &str) -> Vec<String> {
+    let name = match Family::detect(cxx) {
+        Family::Gcc => "gcc",
+        Family::Clang => "clang",
+    };
+
+    let version = Command::new(cxx).arg("-dumpversion").output().ok()
+        .filter(|x| x.status.success())
+        .and_then(|x| String::from_utf8(x.stdout).ok())
+        .and_then(|x| x.trim().split('.').next().map(String::from))
+        .filter(|x| !x.is_empty());
+
+    let mut settings = vec![format!("compiler={name}")];
+    match version {
+        Some(version) => settings.push(format!("compiler.version={version}")),
+        None => warn!("Version of `{cxx}` is unknown, it is taken from the Conan profile."),
+    }
+
+    settings
+}
+
+fn install_cmd(manager: &Manager, build_dir: &Path, args: &Args, cfg: &Config) -> Result<Command, String> {
+    match manager {
+        Manager::Conan => {
+            let mut cmd = Command::new("conan");
+            cmd.args([
+                "install", args.project(),
+                "--output-folder", &build_dir.to_string_lossy(),
+                "--build=missing",
+                "-s", &format!("build_type={}", args.build_type),
+            ]);
+
+            if let Ok(profile) = cfg.get_string("dependencies.profile") {
+                cmd.args(["--profile", &profile]);
+            }
+
+            if let Some(toolchain) = get_toolchain(cfg) {
+                cmd.args([
+                    "-c",
+                    &format!("tools.cmake.cmaketoolchain:user_toolchain=[\"{}\"]", toolchain.path(args.project(), build_dir).display())
+                ]);
+            }
+
+            let cc = get_cc(cfg);
+            let cxx = get_cxx(cfg);
+            if !cc.is_empty() && !cxx.is_empty() {
+                // Packages are resolved for the settings, so they have to match the compiler.
+                for setting in conan_compiler_settings(&cxx) {
+                    cmd.args(["-s", &setting]);
+                }
+                cmd.args(["-c", &format!("tools.build:compiler_executables={{\"c\": \"{cc}\", \"cpp\": \"{cxx}\"}}")]);
+            }
+
+            set_compilers(&mut cmd, cfg);
+            Ok(cmd)
+        },
+        Manager::Vcpkg => {
+            let mut cmd = Command::new(vcpkg_root(cfg)?.join("vcpkg"));
+            cmd.args([
+                "install",
+                &format!("--x-manifest-root={}", args.project()),
+                &format!("--x-install-root={}", build_dir.join("vcpkg_installed").display()),
+            ]);
+
+            set_compilers(&mut cmd, cfg);
+            Ok(cmd)
+        },
+    }
+}
+
+/// Install the dependencies into the build directory.
+///
+/// Installing is skipped if the lockfile (or manifest), the build type and the compiler are the
+/// same as at the last successful install.
+///
+/// Returns whether the dependencies have been installed.
+///
+/// # Errors
+///
+/// Returns an error in the following cases:
+/// - Package manager is not supported or not found
+/// - No lockfile or manifest in the project directory
+/// - Installing fails
+pub fn install(build_dir: &Path, args: &Args, cfg: &Config) -> Result<bool, String> {
+    let Some(manager) = get_manager(cfg)? else {
+        return Ok(false);
+    };
+
+    let lockfile = lockfiles(&manager).iter()
+        .map(|x| Path::new(args.project()).join(x))
+        .find(|x| x.exists())
+        .ok_or_else(|| format!("No dependency manifest found for {manager:?} in `{}`", args.project()))?;
+
+    let content = fs::read(&lockfile).map_err(|e| format!("Failed to read `{}`: {e}", lockfile.display()))?;
+    let fp = fingerprint(&[
+        content.as_slice(),
+        args.build_type.as_bytes(),
+        get_cxx(cfg).as_bytes(),
+    ].concat());
+
+    let stamp = build_dir.join(STAMP);
+    if fs::read_to_string(&stamp).is_ok_and(|x| x == fp) {
+        info!("`{}` is unchanged, skipping installing dependencies.", lockfile.display());
+        return Ok(false);
+    }
+
+    info!("Installing dependencies with {manager:?}...");
+    let mut cmd = install_cmd(&manager, build_dir, args, cfg)?;
+    if !execute_interactive(&mut cmd, args.dry_run)?.success() {
+        return Err("Installing dependencies failed".into());
+    }
+
+    if !args.dry_run {
+        fs::create_dir_all(build_dir).map_err(|e| format!("Failed to create build directory: {e}"))?;
+        fs::write(&stamp, fp).map_err(|e| format!("Failed to write `{}`: {e}", stamp.display()))?;
+    }
+
+    Ok(true)
+}
+
+/// CMake definitions wiring in the installed dependencies.
+///
+/// The user toolchain is not overridden, but chained into the package manager's one.
+///
+/// # Errors
+///
+/// Returns an error if the package manager is not supported, or the vcpkg root is not known.
+///
+/// # Panics
+///
+/// Panics if the current working directory is not accessible.
+pub fn cmake_definitions(build_dir: &Path, args: &Args, cfg: &Config) -> Result<Vec<String>, String> {
+    let build_dir = path::absolute(build_dir).expect("Cannot access current working directory");
+    let build_dir = build_dir.as_path();
+    let toolchain = get_toolchain(cfg).map(|x| x.path(args.project(), build_dir));
+
+    match get_manager(cfg)? {
+        Some(Manager::Conan) => {
+            // Location depends on the layout used by the conanfile, e.g. `cmake_layout`.
+            let file = [
+                build_dir.to_path_buf(),
+                build_dir.join(&args.build_type).join("generators"),
+                build_dir.join("build").join(&args.build_type).join("generators"),
+            ]
+                .into_iter()
+                .map(|x| x.join("conan_toolchain.cmake"))
+                .find(|x| x.exists())
+                .unwrap_or_else(|| build_dir.join("conan_toolchain.cmake"));
+
+            Ok(vec![format!("CMAKE_TOOLCHAIN_FILE={}", file.display())])
+        },
+        Some(Manager::Vcpkg) => {
+            let root = vcpkg_root(cfg)?;
+
+            let mut definitions = vec![
+                format!("CMAKE_TOOLCHAIN_FILE={}", root.join("scripts/buildsystems/vcpkg.cmake").display()),
+                format!("VCPKG_MANIFEST_DIR={}", path::absolute(args.project()).expect("Cannot access current working directory").display()),
+                format!("VCPKG_INSTALLED_DIR={}", build_dir.join("vcpkg_installed").display()),
+                "VCPKG_MANIFEST_INSTALL=OFF".to_string(),
+            ];
+
+            if let Some(toolchain) = toolchain {
+                definitions.push(format!("VCPKG_CHAINLOAD_TOOLCHAIN_FILE={}", toolchain.display()));
+            }
+
+            Ok(definitions)
+        },
+        None => Ok(toolchain
+            .map(|x| vec![format!("CMAKE_TOOLCHAIN_FILE={}", x.display())])
+            .unwrap_or_default()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn args() -> Args {
+        Args::parse_from(["baldr", "--project", "project"])
+    }
+
+    #[test]
+    fn unsupported_manager() {
+        let cfg = Config::builder().set_override("dependencies.manager", "npm").unwrap().build().unwrap();
+        assert!(get_manager(&cfg).is_err());
+        assert!(cmake_definitions(Path::new("/build"), &args(), &cfg).is_err());
+    }
+
+    #[test]
+    fn conan_layout() {
+        let dir = env::temp_dir().join(format!("baldr-conan-{}", std::process::id()));
+        let cfg = Config::builder().set_override("dependencies.manager", "conan").unwrap().build().unwrap();
+
+        assert_eq!(
+            cmake_definitions(&dir, &args(), &cfg).unwrap(),
+            [format!("CMAKE_TOOLCHAIN_FILE={}/conan_toolchain.cmake", dir.display())]
+        );
+
+        let generators = dir.join("build/Debug/generators");
+        fs::create_dir_all(&generators).unwrap();
+        fs::write(generators.join("conan_toolchain.cmake"), "").unwrap();
+
+        assert_eq!(
+            cmake_definitions(&dir, &args(), &cfg).unwrap(),
+            [format!("CMAKE_TOOLCHAIN_FILE={}/conan_toolchain.cmake", generators.display())]
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn conan_compiler() {
+        assert_eq!(conan_compiler_settings("/nonexistent/clang++-18"), ["compiler=clang"]);
+        assert_eq!(conan_compiler_settings("/nonexistent/g++")[0], "compiler=gcc");
+    }
+
+    #[test]
+    fn vcpkg_chainload() {
+        let cfg = Config::builder()
+            .set_override("dependencies.manager", "vcpkg").unwrap()
+            .set_override("dependencies.vcpkg_root", "/opt/vcpkg").unwrap()
+            .set_override("toolchain.file", "/cross.cmake").unwrap()
+            .build().unwrap();
+
+        let definitions = cmake_definitions(Path::new("/build"), &args(), &cfg).unwrap();
+
+        assert_eq!(definitions[0], "CMAKE_TOOLCHAIN_FILE=/opt/vcpkg/scripts/buildsystems/vcpkg.cmake");
+        assert!(definitions[1].ends_with("/project"));
+        assert_eq!(definitions[2], "VCPKG_INSTALLED_DIR=/build/vcpkg_installed");
+        assert_eq!(definitions[3], "VCPKG_MANIFEST_INSTALL=OFF");
+        assert_eq!(definitions[4], "VCPKG_CHAINLOAD_TOOLCHAIN_FILE=/cross.cmake");
+    }
+}
@@ -0,0 +1,171 @@
+//! Management of the build directories of a project.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use walkdir::WalkDir;
+
+use crate::cmake_cache;
+
+/// A build directory under `<project>/build`.
+#[derive(Debug)]
+pub struct BuildDir {
+    pub path: PathBuf,
+    pub name: String,
+    pub build_type: Option<String>,
+    pub compiler: Option<String>,
+    pub generator: Option<String>,
+
+    /// Total size of the files in bytes
+    pub size: u64,
+
+    /// Last modification of any file in the directory
+    pub last_build: Option<SystemTime>,
+
+    /// Whether `compile_commands.json` of the project points into this directory
+    pub linked: bool,
+}
+
+impl BuildDir {
+    /// Time elapsed since the last build.
+    pub fn age(&self) -> Option<Duration> {
+        self.last_build.and_then(|x| x.elapsed().ok())
+    }
+}
+
+/// Build directory `compile_commands.json` of the project points into.
+pub fn linked_dir(project: &str) -> Option<PathBuf> {
+    let target = fs::canonicalize(Path::new(project).join("compile_commands.json")).ok()?;
+    target.parent().map(Path::to_path_buf)
+}
+
+fn inspect(path: &Path, linked: Option<&Path>) -> BuildDir {
+    let cache = cmake_cache::read(path).unwrap_or_default();
+    let get = |name: &str| {
+        cmake_cache::get(&cache, name)
+            .filter(|x| !x.is_empty())
+            .map(String::from)
+    };
+
+    let mut size = 0;
+    let mut last_build = None;
+
+    for entry in WalkDir::new(path).into_iter().filter_map(|e| e.ok()) {
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+
+        if metadata.is_file() {
+            size += metadata.len();
+            last_build = last_build.max(metadata.modified().ok());
+        }
+    }
+
+    BuildDir {
+        path: path.to_path_buf(),
+        name: path.file_name().map(|x| x.to_string_lossy().into_owned()).unwrap_or_default(),
+        build_type: get("CMAKE_BUILD_TYPE"),
+        compiler: get("CMAKE_CXX_COMPILER"),
+        generator: get("CMAKE_GENERATOR"),
+        size,
+        last_build,
+        linked: linked.is_some_and(|x| fs::canonicalize(path).is_ok_and(|p| p == x)),
+    }
+}
+
+/// List the build directories of the project sorted by name.
+///
+/// # Errors
+///
+/// Returns an error if the build root exists but cannot be read.
+pub fn list(project: &str) -> Result<Vec<BuildDir>, String> {
+    let root = Path::new(project).join("build");
+    if !root.exists() {
+        return Ok(vec![]);
+    }
+
+    let linked = linked_dir(project);
+    let mut dirs = fs::read_dir(&root)
+        .map_err(|e| format!("Failed to read `{}`: {e}", root.display()))?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_dir())
+        .map(|e| inspect(&e.path(), linked.as_deref()))
+        .collect::<Vec<_>>();
+
+    dirs.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(dirs)
+}
+
+/// Match a name against a pattern with `*` (any sequence) and `?` (any character) wildcards.
+///
+/// ```
+/// use baldr::dirs::matches;
+///
+/// assert!(matches("release-*", "release-clang++"));
+/// assert!(matches("debug-g++-?san", "debug-g++-asan"));
+/// assert!(!matches("release-*", "debug"));
+/// ```
+pub fn matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // Position after the last `*` and the name position it is matched up to.
+    let mut backtrack: Option<(usize, usize)> = None;
+    let (mut p, mut n) = (0, 0);
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p + 1, n));
+                p += 1;
+            },
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            },
+            _ => match backtrack {
+                Some((bp, bn)) => {
+                    backtrack = Some((bp, bn + 1));
+                    p = bp;
+                    n = bn + 1;
+                },
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|x| *x == '*')
+}
+
+/// Format an age in a human readable way, e.g. `3d ago`.
+pub fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{}m ago", secs / 60),
+        3600..86400 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wildcards() {
+        assert!(matches("*", ""));
+        assert!(matches("*-asan", "debug-g++-asan"));
+        assert!(matches("d*g*-*", "debug-gcc-tsan"));
+        assert!(!matches("debug", "debug-gcc"));
+        assert!(!matches("?", ""));
+    }
+
+    #[test]
+    fn ages() {
+        assert_eq!(format_age(Duration::from_secs(5)), "just now");
+        assert_eq!(format_age(Duration::from_secs(3 * 3600 + 5)), "3h ago");
+        assert_eq!(format_age(Duration::from_secs(2 * 86400)), "2d ago");
+    }
+}
@@ -0,0 +1,203 @@
+//! Formatting of the C/C++ sources with `clang-format`.
+//!
+//! The style is configured by the usual `.clang-format` files, which `clang-format` looks up itself.
+//!
+//! ```yaml
+//! format:
+//!   binary: clang-format-18
+//!   # paths relative to the project, `*` and `?` wildcards
+//!   exclude:
+//!     - generated/*
+//! ```
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use config::Config;
+use log::*;
+
+use crate::{
+    Args,
+    dirs,
+    execute,
+    find_files_skipping,
+    format_cmd,
+    get_list,
+    git,
+};
+
+/// Extensions of C/C++ sources and headers.
+const EXTENSIONS: [&str; 12] = ["c", "cc", "cpp", "cxx", "c++", "h", "hh", "hpp", "hxx", "h++", "ipp", "inl"];
+
+/// Files passed to a single in-place invocation, to stay below the command line limit.
+const CHUNK_SIZE: usize = 100;
+
+/// Whether the file is a C/C++ source or header.
+pub fn is_source(file_name: &str) -> bool {
+    Path::new(file_name)
+        .extension()
+        .and_then(|x| x.to_str())
+        .is_some_and(|x| EXTENSIONS.contains(&x))
+}
+
+/// Whether the file or directory should not be formatted, i.e. it is hidden, in `build/` or excluded.
+fn is_excluded(path: &Path, project: &Path, excludes: &[String]) -> bool {
+    let Ok(relative) = path.strip_prefix(project) else {
+        return true;
+    };
+
+    let relative = relative.to_string_lossy();
+    relative == "build"
+        || relative.starts_with("build/")
+        || path.file_name().is_some_and(|x| x.to_string_lossy().starts_with('.'))
+        || excludes.iter().any(|x| dirs::matches(x, &relative))
+}
+
+/// Sources of the project sorted by path.
+pub fn sources(project: &Path, excludes: &[String]) -> Vec<PathBuf> {
+    let mut files = find_files_skipping(&project.to_path_buf(), |dir| is_excluded(dir, project, excludes), is_source)
+        .into_iter()
+        .map(PathBuf::from)
+        .filter(|x| !is_excluded(x, project, excludes))
+        .collect::<Vec<_>>();
+
+    files.sort();
+    files
+}
+
+/// Unified diff between a file and its formatted content, empty if they are the same.
+fn diff(file: &Path, label: &str, formatted: &[u8]) -> Result<String, String> {
+    let mut cmd = Command::new("diff");
+    cmd.args(["-u", "--label", &format!("a/{label}"), "--label", &format!("b/{label}")]);
+    cmd.arg(file).arg("-");
+    cmd.stdin(Stdio::piped()).stdout(Stdio::piped());
+
+    let cmd_str = format_cmd(&cmd);
+    let mut child = cmd.spawn().map_err(|e| format!("Spawning command `{cmd_str}` failed with `{e}`"))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(formatted).map_err(|e| format!("Failed to write to `{cmd_str}`: {e}"))?;
+    }
+
+    let output = child.wait_with_output().map_err(|e| format!("Command `{cmd_str}` failed with `{e}`"))?;
+
+    // `diff` exits with 1 if the files differ.
+    match output.status.code() {
+        Some(0 | 1) => Ok(String::from_utf8_lossy(&output.stdout).into_owned()),
+        _ => Err(format!("Command `{cmd_str}` has failed")),
+    }
+}
+
+/// Print the violations of the files as a unified diff.
+///
+/// Returns the number of files which need formatting.
+fn check(binary: &str, project: &Path, files: &[PathBuf]) -> Result<usize, String> {
+    let mut violations = 0;
+
+    for file in files {
+        let mut cmd = Command::new(binary);
+        cmd.arg(file);
+        debug!("CMD: {}", format_cmd(&cmd));
+
+        let output = cmd.output().map_err(|e| format!("Failed to run `{binary}` on `{}`: {e}", file.display()))?;
+        if !output.status.success() {
+            return Err(format!(
+                "`{binary}` has failed on `{}`: {}",
+                file.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        let content = fs::read(file).map_err(|e| format!("Failed to read `{}`: {e}", file.display()))?;
+        if content == output.stdout {
+            continue;
+        }
+
+        let label = file.strip_prefix(project).unwrap_or(file).to_string_lossy();
+        print!("{}", diff(file, &label, &output.stdout)?);
+        violations += 1;
+    }
+
+    Ok(violations)
+}
+
+/// Run `clang-format` on the sources of the project, either in place or checking them.
+///
+/// # Errors
+///
+/// Returns an error if changed files cannot be queried from git, `clang-format` fails, or in check
+/// mode, if any file needs formatting.
+pub fn run(args: &Args, config: &Config, check_only: bool, since: Option<&str>) -> Result<(), String> {
+    let project = fs::canonicalize(args.project())
+        .map_err(|e| format!("Invalid project directory `{}`: {e}", args.project()))?;
+
+    let excludes = get_list(config, "format.exclude");
+    let mut files = sources(&project, &excludes);
+
+    if let Some(reference) = since {
+        let changed = git::changed_files(&project, reference)?;
+        files.retain(|x| changed.contains(x));
+        info!("{} source(s) changed since `{reference}`.", files.len());
+    }
+
+    let binary = config.get_string("format.binary").unwrap_or_else(|_| "clang-format".into());
+
+    if check_only {
+        if args.dry_run {
+            for file in &files {
+                execute(Command::new(&binary).arg(file), true)?;
+            }
+            return Ok(());
+        }
+
+        info!("Checking the formatting of {} source(s) with `{binary}`...", files.len());
+        let violations = check(&binary, &project, &files)?;
+        println!("clang-format: {} source(s) checked, {violations} need(s) formatting", files.len());
+
+        if violations > 0 {
+            return Err(format!("{violations} file(s) need formatting, run `baldr format` to fix them"));
+        }
+
+        return Ok(());
+    }
+
+    info!("Formatting {} source(s) with `{binary}`...", files.len());
+
+    for chunk in files.chunks(CHUNK_SIZE) {
+        let status = execute(Command::new(&binary).arg("-i").args(chunk), args.dry_run)?;
+        if !status.success() {
+            return Err(format!("`{binary}` has failed with {status}"));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn source_extensions() {
+        assert!(is_source("main.cc"));
+        assert!(is_source("lib.h++"));
+        assert!(is_source("detail.ipp"));
+        assert!(!is_source("CMakeLists.txt"));
+        assert!(!is_source("Makefile"));
+    }
+
+    #[test]
+    fn exclusions() {
+        let project = Path::new("/project");
+        let excludes = vec!["generated/*".to_string()];
+
+        assert!(!is_excluded(Path::new("/project/src/main.cc"), project, &excludes));
+        assert!(is_excluded(Path::new("/project/build"), project, &excludes));
+        assert!(is_excluded(Path::new("/project/build/debug/x.cc"), project, &excludes));
+        assert!(is_excluded(Path::new("/project/.git"), project, &excludes));
+        assert!(is_excluded(Path::new("/project/generated/x.cc"), project, &excludes));
+        assert!(!is_excluded(Path::new("/project/builder/x.cc"), project, &excludes));
+    }
+}
@@ -0,0 +1,46 @@
+//! Querying the git repository of the project.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::format_cmd;
+
+fn output(cmd: &mut Command) -> Result<String, String> {
+    let cmd_str = format_cmd(cmd);
+    let output = cmd.output().map_err(|e| format!("Spawning command `{cmd_str}` failed with `{e}`"))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "Command `{cmd_str}` failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Files changed since the given ref, including uncommitted and untracked files.
+///
+/// Deleted files are not listed. The paths are absolute.
+///
+/// # Errors
+///
+/// Returns an error if the project is not in a git repository or the ref is unknown.
+pub fn changed_files(project: &Path, reference: &str) -> Result<Vec<PathBuf>, String> {
+    let root = output(Command::new("git").arg("-C").arg(project).args(["rev-parse", "--show-toplevel"]))?;
+    let root = PathBuf::from(root.trim());
+
+    let changed = output(
+        Command::new("git").arg("-C").arg(&root).args(["diff", "--name-only", "--diff-filter=d", reference, "--"])
+    )?;
+
+    let untracked = output(
+        Command::new("git").arg("-C").arg(&root).args(["ls-files", "--others", "--exclude-standard"])
+    )?;
+
+    Ok(changed.lines()
+        .chain(untracked.lines())
+        .filter(|x| !x.is_empty())
+        .map(|x| root.join(x))
+        .collect())
+}
@@ -0,0 +1,121 @@
+//! Shell commands run before and after the phases of the pipeline.
+//!
+//! ```yaml
+//! hooks:
+//!   pre_configure: ./scripts/generate.sh
+//!   post_build:
+//!     - strip "$BALDR_EXE"
+//!     - du -h "$BALDR_EXE"
+//!   # also: post_configure, pre_build, pre_run, post_run
+//! ```
+//!
+//! Commands run with `sh -c` in the project directory, with the following variables set:
+//! * `BALDR_PROJECT`
+//! * `BALDR_BUILD_DIR`
+//! * `BALDR_BUILD_TYPE`
+//! * `BALDR_TARGET`
+//! * `BALDR_EXE` (path of the built executable found like for running it, unset if there is none)
+//!
+//! Hooks stay in the foreground process group, so they can prompt on the terminal (e.g. `sudo`).
+//! A failing hook aborts the pipeline.
+
+use std::path::{self, Path};
+use std::process::Command;
+
+use config::Config;
+use log::*;
+
+use crate::{
+    Args,
+    execute_interactive,
+    find_executable,
+    get_list,
+};
+
+/// Commands of a hook, given either as a single string or a list.
+pub fn get_commands(cfg: &Config, hook: &str) -> Vec<String> {
+    let key = format!("hooks.{hook}");
+    match cfg.get_string(&key) {
+        Ok(x) => vec![x],
+        Err(_) => get_list(cfg, &key),
+    }
+}
+
+/// Assemble the command of a hook.
+pub fn hook_cmd(command: &str, build_dir: &Path, args: &Args) -> Command {
+    let absolute = |x: &Path| path::absolute(x).unwrap_or_else(|_| x.to_path_buf());
+
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", command]);
+    cmd.current_dir(args.project());
+    cmd.env("BALDR_PROJECT", absolute(Path::new(args.project())));
+    cmd.env("BALDR_BUILD_DIR", absolute(build_dir));
+    cmd.env("BALDR_BUILD_TYPE", &args.build_type);
+    cmd.env("BALDR_TARGET", &args.target);
+
+    // E.g. not built yet, or the target is not a single executable.
+    match find_executable(build_dir, &args.target, args.dry_run) {
+        Ok(exe) => cmd.env("BALDR_EXE", exe),
+        Err(_) => cmd.env_remove("BALDR_EXE"),
+    };
+
+    cmd
+}
+
+/// Run the commands of a hook in order, e.g. `pre_build`.
+///
+/// # Errors
+///
+/// Returns an error naming the hook if any of its commands cannot be started or fails.
+pub fn run(hook: &str, build_dir: &Path, args: &Args, cfg: &Config) -> Result<(), String> {
+    for command in get_commands(cfg, hook) {
+        info!("Running hook `{hook}`: {command}");
+
+        let status = execute_interactive(&mut hook_cmd(&command, build_dir, args), args.dry_run)
+            .map_err(|e| format!("Hook `{hook}` cannot be run: {e}"))?;
+
+        if !status.success() {
+            return Err(format!("Hook `{hook}` (`{command}`) has failed with {status}"));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands() {
+        let cfg = Config::builder()
+            .set_override("hooks.pre_build", "echo pre").unwrap()
+            .set_override("hooks.post_build", vec!["echo one", "echo two"]).unwrap()
+            .build().unwrap();
+
+        assert_eq!(get_commands(&cfg, "pre_build"), ["echo pre"]);
+        assert_eq!(get_commands(&cfg, "post_build"), ["echo one", "echo two"]);
+        assert!(get_commands(&cfg, "pre_run").is_empty());
+    }
+
+    #[test]
+    fn executable() {
+        use clap::Parser;
+
+        let build_dir = std::env::temp_dir().join(format!("baldr-hooks-{}", std::process::id()));
+        std::fs::create_dir_all(build_dir.join("bin")).unwrap();
+        std::fs::write(build_dir.join("bin/app"), "").unwrap();
+
+        let exe = |target: &str| {
+            let args = Args::parse_from(["baldr", "--project", ".", "--target", target]);
+            hook_cmd("true", &build_dir, &args).get_envs()
+                .find(|(k, _)| *k == "BALDR_EXE")
+                .and_then(|(_, v)| v.map(std::path::PathBuf::from))
+        };
+
+        assert_eq!(exe("app"), Some(build_dir.join("bin/app")));
+        assert_eq!(exe("other"), None);
+
+        std::fs::remove_dir_all(build_dir).unwrap();
+    }
+}
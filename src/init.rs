@@ -0,0 +1,363 @@
+//! Scaffolding a configuration file from the environment and the project.
+
+use std::collections::BTreeSet;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::*;
+
+use crate::{
+    Args,
+    dirs,
+    global_config_dir,
+    lookup_project,
+    read_input,
+};
+
+/// Tools found on `PATH`.
+#[derive(Debug, Default)]
+pub struct Environment {
+    /// C and C++ compiler pairs, e.g. `("clang-18", "clang++-18")`
+    pub compilers: Vec<(String, String)>,
+    pub debugger: Option<String>,
+    pub ninja: bool,
+    pub ccache: bool,
+}
+
+/// What the project already has.
+#[derive(Debug, Default)]
+pub struct Project {
+    /// Names of the configure presets in `CMakePresets.json` and `CMakeUserPresets.json`
+    pub presets: Vec<String>,
+
+    /// Names of the build directories under `<project>/build`
+    pub build_dirs: Vec<String>,
+}
+
+/// Executables in the directories of `PATH`.
+fn executables() -> BTreeSet<String> {
+    let Some(paths) = env::var_os("PATH") else {
+        return BTreeSet::new();
+    };
+
+    env::split_paths(&paths)
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_file())
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .collect()
+}
+
+/// Pair the C++ compilers with their C counterparts, the default (unversioned) ones first.
+///
+/// Only native compilers are considered, i.e. `g++`, `clang++` and their versioned variants like
+/// `g++-13`.
+pub fn find_compilers(executables: &BTreeSet<String>) -> Vec<(String, String)> {
+    let mut compilers = executables.iter()
+        .filter_map(|cxx| {
+            let (prefix, version) = ["g++", "clang++"].iter()
+                .find_map(|p| cxx.strip_prefix(p).map(|v| (*p, v)))?;
+
+            if !version.is_empty() && !version.strip_prefix('-').is_some_and(|v| v.chars().all(|c| c.is_ascii_digit() || c == '.')) {
+                return None;
+            }
+
+            let cc = format!("{}{version}", if prefix == "g++" { "gcc" } else { "clang" });
+            executables.contains(&cc).then(|| (cc, cxx.clone()))
+        })
+        .collect::<Vec<_>>();
+
+    compilers.sort_by_key(|(_, cxx)| (cxx.contains('-'), cxx.clone()));
+    compilers
+}
+
+/// Inspect the tools available on `PATH`.
+pub fn detect_environment() -> Environment {
+    let executables = executables();
+
+    Environment {
+        compilers: find_compilers(&executables),
+        debugger: ["gdb", "lldb"].iter().find(|x| executables.contains(**x)).map(|x| x.to_string()),
+        ninja: executables.contains("ninja"),
+        ccache: executables.contains("ccache"),
+    }
+}
+
+/// Inspect the presets and build directories of the project.
+pub fn detect_project(project: &Path) -> Project {
+    let presets = ["CMakePresets.json", "CMakeUserPresets.json"].iter()
+        .filter_map(|x| fs::read_to_string(project.join(x)).ok())
+        .filter_map(|x| serde_json::from_str::<serde_json::Value>(&x).ok())
+        .flat_map(|json| {
+            json["configurePresets"].as_array().cloned().unwrap_or_default()
+                .iter()
+                .filter(|x| !x["hidden"].as_bool().unwrap_or(false))
+                .filter_map(|x| x["name"].as_str().map(String::from))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    let build_dirs = dirs::list(&project.to_string_lossy())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|x| x.name)
+        .collect();
+
+    Project { presets, build_dirs }
+}
+
+/// Render the commented configuration file.
+///
+/// Detected settings are active, the rest of the supported keys are commented out as examples.
+pub fn render(environment: &Environment, project: Option<&Project>) -> String {
+    let mut out = String::from(
+        "# Configuration of baldr, generated by `baldr init`.\n\
+         # Commented out keys show examples, uncomment them to use.\n\n"
+    );
+
+    match environment.compilers.first() {
+        Some((cc, cxx)) => out += &format!("compiler:\n  cc: {cc}\n  cxx: {cxx}\n"),
+        None => out += "# compiler:\n#   cc: gcc\n#   cxx: g++\n",
+    }
+
+    if environment.compilers.len() > 1 {
+        let others = environment.compilers[1..].iter()
+            .map(|(_, cxx)| cxx.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        out += &format!("# Other compilers found: {others}\n");
+    }
+
+    match &environment.debugger {
+        Some(x) => out += &format!("\ndebugger: {x}\n"),
+        None => out += "\n# debugger: gdb  # or lldb\n",
+    }
+
+    if environment.ccache {
+        out += "\n# Given to CMake configuring.\ncmake:\n  definitions:\n    - CMAKE_C_COMPILER_LAUNCHER=ccache\n    - CMAKE_CXX_COMPILER_LAUNCHER=ccache\n";
+    } else {
+        out += "\n# cmake:\n#   definitions:\n#     - MY_OPTION=ON\n";
+    }
+
+    if environment.ninja {
+        out += "\n# Ninja is found, CMake uses it if `CMAKE_GENERATOR=Ninja` is set in the environment.\n";
+    }
+
+    if let Some(project) = project {
+        out += "\n# Personal overrides can be put into `.baldr.local.yaml`, which is better git-ignored.\n";
+
+        if !project.presets.is_empty() {
+            out += &format!(
+                "\n# CMake presets found: {}\n# Their cache variables can be given in `cmake.definitions`.\n",
+                project.presets.join(", ")
+            );
+        }
+
+        if !project.build_dirs.is_empty() {
+            out += &format!("\n# Existing build directories: {}\n", project.build_dirs.join(", "));
+        }
+    }
+
+    out += "\n\
+        # Parallel jobs default to the available parallelism.\n\
+        # build:\n\
+        #   jobs: 8\n\
+        #   memory_per_job: 2G\n\
+        #   load_average: 8\n\
+        \n\
+        # Logs of configuring, building and running kept in `<build-dir>/logs`, see `baldr log`.\n\
+        # log:\n\
+        #   keep: 20\n\
+        #   run: true\n\
+        \n\
+        # Keep `compile_commands.json` pointing to the most recent build.\n\
+        # compile_commands:\n\
+        #   follow: true\n\
+        \n\
+        # Cross-compilation with a toolchain file, or an inline target and sysroot.\n\
+        # toolchain:\n\
+        #   file: cmake/aarch64.cmake\n\
+        #   target: aarch64-linux-gnu\n\
+        #   sysroot: /opt/sysroot/aarch64\n\
+        #   emulator: qemu-aarch64 -L /opt/sysroot/aarch64\n\
+        \n\
+        # dependencies:\n\
+        #   manager: conan  # or vcpkg\n\
+        #   profile: default\n\
+        \n\
+        # Shell commands around the phases: pre_/post_ configure, build and run.\n\
+        # hooks:\n\
+        #   post_build: du -h \"$BALDR_EXE\"\n\
+        \n\
+        # Environment, working directory, timeout and resource limits of the executable.\n\
+        # run:\n\
+        #   env:\n\
+        #     - LOG_LEVEL=debug\n\
+        #   env_file: .env\n\
+        #   cwd: data\n\
+        #   timeout: 30s\n\
+        #   limits:\n\
+        #     memory: 512M\n\
+        #     cpu: 60\n\
+        #     files: 256\n\
+        \n\
+        # Named run configurations, e.g. `baldr run leaks -- --input data.txt`.\n\
+        # runs:\n\
+        #   leaks:\n\
+        #     target: app\n\
+        #     args: --verbose\n\
+        #     wrapper: valgrind --leak-check=full\n\
+        #     # also: env, env_file, unset, cwd, timeout, limits and debugger\n\
+        \n\
+        # tidy:\n\
+        #   binary: clang-tidy\n\
+        #   exclude:\n\
+        #     - generated/*\n\
+        \n\
+        # format:\n\
+        #   binary: clang-format\n\
+        #   exclude:\n\
+        #     - generated/*\n\
+        \n\
+        # coverage:\n\
+        #   tool: gcovr  # gcovr or lcov for GCC, llvm-cov for Clang\n\
+        #   include:\n\
+        #     - src/*\n\
+        \n\
+        # package:\n\
+        #   generators:\n\
+        #     - TGZ\n\
+        \n\
+        # bench:\n\
+        #   warmup: 1\n\
+        #   threshold: 5\n\
+        \n\
+        # Member projects built in dependency order by `baldr workspace build`, installed into the\n\
+        # staging prefix (`<build-dir>/staging` by default) passed to dependents in CMAKE_PREFIX_PATH.\n\
+        # workspace:\n\
+        #   members:\n\
+        #     core:\n\
+        #       path: libs/core\n\
+        #     app:\n\
+        #       path: apps/app\n\
+        #       depends: [core]\n";
+
+    out
+}
+
+/// Path of the configuration file to write.
+///
+/// The global file is `$XDG_CONFIG_HOME/baldr/config.yaml`, see [`global_config_dir`].
+///
+/// # Errors
+///
+/// Returns an error if neither `XDG_CONFIG_HOME` nor `HOME` is defined for the global file.
+pub fn config_path(project: &Path, global: bool) -> Result<PathBuf, String> {
+    if !global {
+        return Ok(project.join(".baldr.yaml"));
+    }
+
+    global_config_dir()
+        .map(|x| x.join("config.yaml"))
+        .ok_or_else(|| "Neither `XDG_CONFIG_HOME` nor `HOME` is defined".to_string())
+}
+
+/// Write a commented configuration file for the project, or a global one.
+///
+/// An existing file is only overwritten after confirmation.
+///
+/// # Errors
+///
+/// Returns an error if the file exists and overwriting is not confirmed, or it cannot be written.
+pub fn run(args: &Args, global: bool) -> Result<(), String> {
+    let project = match &args.project {
+        Some(x) => PathBuf::from(x),
+        None => lookup_project()?.unwrap_or_else(|| PathBuf::from(".")),
+    };
+
+    let file = config_path(&project, global)?;
+    let environment = detect_environment();
+    let content = if global {
+        render(&environment, None)
+    } else {
+        render(&environment, Some(&detect_project(&project)))
+    };
+
+    if args.dry_run {
+        print!("cat > {} <<'EOF'\n{content}EOF\n", file.display());
+        return Ok(());
+    }
+
+    if file.exists() {
+        if args.no_confirm {
+            info!("Non-interactive mode, overwriting `{}`.", file.display());
+        } else {
+            eprint!("`{}` already exists, overwrite it (press 'y' to proceed): ", file.display());
+
+            if read_input().as_deref() != Some("y") {
+                return Err(format!("`{}` already exists, not overwriting it", file.display()));
+            }
+        }
+    }
+
+    if let Some(dir) = file.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create `{}`: {e}", dir.display()))?;
+    }
+
+    fs::write(&file, content).map_err(|e| format!("Failed to write `{}`: {e}", file.display()))?;
+    println!("Configuration is written to `{}`.", file.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compilers() {
+        let executables = ["g++", "gcc", "clang++-18", "clang-18", "clang++", "aarch64-linux-gnu-g++", "g++-13", "g++-wrapper"]
+            .map(String::from)
+            .into_iter()
+            .collect();
+
+        assert_eq!(
+            find_compilers(&executables),
+            vec![
+                ("gcc".to_string(), "g++".to_string()),
+                ("clang-18".to_string(), "clang++-18".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn rendered_config_is_valid() {
+        let environment = Environment {
+            compilers: vec![("clang".into(), "clang++".into()), ("gcc".into(), "g++".into())],
+            debugger: Some("lldb".into()),
+            ninja: true,
+            ccache: true,
+        };
+        let project = Project { presets: vec!["dev".into()], build_dirs: vec!["debug-clang++".into()] };
+
+        let content = render(&environment, Some(&project));
+        let cfg = config::Config::builder()
+            .add_source(config::File::from_str(&content, config::FileFormat::Yaml))
+            .build()
+            .unwrap();
+
+        assert_eq!(cfg.get_string("compiler.cxx").unwrap(), "clang++");
+        assert_eq!(cfg.get_string("debugger").unwrap(), "lldb");
+        assert!(content.contains("`CMAKE_GENERATOR=Ninja`"));
+        assert_eq!(cfg.get_array("cmake.definitions").unwrap().len(), 2);
+        assert!(content.contains("# Other compilers found: g++"));
+        assert!(content.contains("# CMake presets found: dev"));
+
+        let empty = render(&Environment::default(), None);
+        assert!(config::Config::builder()
+            .add_source(config::File::from_str(&empty, config::FileFormat::Yaml))
+            .build()
+            .is_ok());
+    }
+}
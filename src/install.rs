@@ -0,0 +1,154 @@
+//! Staging the built artifacts with `cmake --install` and packaging them with `cpack`.
+//!
+//! ```yaml
+//! package:
+//!   # CPack generators, the project's `CPACK_GENERATOR` is used by default
+//!   generators:
+//!     - TGZ
+//!     - DEB
+//! ```
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::SystemTime;
+
+use config::Config;
+use log::*;
+
+use crate::{
+    Args,
+    execute,
+    get_list,
+};
+
+fn ensure_built(build_dir: &Path) -> Result<(), String> {
+    if !build_dir.join("CMakeCache.txt").exists() {
+        return Err(format!("Build directory `{}` is not configured, build the project first", build_dir.display()));
+    }
+    Ok(())
+}
+
+/// Assemble CMake's install command.
+pub fn install_cmd(build_dir: &Path, args: &Args, prefix: &str, component: Option<&str>) -> Command {
+    let mut cmd = Command::new("cmake");
+    cmd.arg("--install").arg(build_dir);
+    cmd.args(["--config", &args.build_type]);
+    cmd.args(["--prefix", prefix]);
+
+    if let Some(component) = component {
+        cmd.args(["--component", component]);
+    }
+
+    cmd
+}
+
+/// Manifest of the installed files CMake writes into the build directory.
+fn manifest(build_dir: &Path, component: Option<&str>) -> PathBuf {
+    match component {
+        Some(x) => build_dir.join(format!("install_manifest_{x}.txt")),
+        None => build_dir.join("install_manifest.txt"),
+    }
+}
+
+/// Install the artifacts of the build directory into the prefix, and list the installed files.
+///
+/// # Errors
+///
+/// Returns an error if the build directory is not configured, or installing has failed.
+pub fn install(build_dir: &Path, args: &Args, prefix: &str, component: Option<&str>) -> Result<(), String> {
+    if !args.dry_run {
+        ensure_built(build_dir)?;
+    }
+
+    let status = execute(&mut install_cmd(build_dir, args, prefix, component), args.dry_run)?;
+    if !status.success() {
+        return Err("Installing failed".into());
+    }
+
+    if args.dry_run {
+        return Ok(());
+    }
+
+    let file = manifest(build_dir, component);
+    match fs::read_to_string(&file) {
+        Ok(content) => {
+            println!("Installed files:");
+            for line in content.lines().filter(|x| !x.is_empty()) {
+                println!("  {line}");
+            }
+        },
+        Err(e) => warn!("Failed to read the list of installed files from `{}`: {e}", file.display()),
+    }
+
+    Ok(())
+}
+
+/// Assemble the `cpack` command, packages are generated into `<build-dir>/packages`.
+pub fn package_cmd(build_dir: &Path, args: &Args, config: &Config) -> Command {
+    let mut cmd = Command::new("cpack");
+    cmd.arg("--config").arg(build_dir.join("CPackConfig.cmake"));
+    cmd.args(["-C", &args.build_type]);
+    cmd.arg("-B").arg(build_dir.join("packages"));
+
+    let generators = get_list(config, "package.generators");
+    if !generators.is_empty() {
+        cmd.args(["-G", &generators.join(";")]);
+    }
+
+    cmd
+}
+
+/// Files directly in the directory modified since the given time, sorted by path.
+fn produced_since(dir: &Path, since: SystemTime) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![];
+    };
+
+    let mut files = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.metadata().is_ok_and(|m| m.is_file() && m.modified().is_ok_and(|t| t >= since)))
+        .map(|e| e.path())
+        .collect::<Vec<_>>();
+
+    files.sort();
+    files
+}
+
+/// Package the build directory with `cpack`, and list the produced packages.
+///
+/// # Errors
+///
+/// Returns an error if the build directory is not configured, the project does not use CPack, or
+/// packaging has failed.
+pub fn package(build_dir: &Path, args: &Args, config: &Config) -> Result<(), String> {
+    if !args.dry_run {
+        ensure_built(build_dir)?;
+
+        if !build_dir.join("CPackConfig.cmake").exists() {
+            return Err("The project does not use CPack, `include(CPack)` is missing from CMakeLists.txt".into());
+        }
+    }
+
+    let start = SystemTime::now();
+    let status = execute(&mut package_cmd(build_dir, args, config), args.dry_run)?;
+    if !status.success() {
+        return Err("Packaging failed".into());
+    }
+
+    if args.dry_run {
+        return Ok(());
+    }
+
+    let packages = produced_since(&build_dir.join("packages"), start);
+    if packages.is_empty() {
+        warn!("No package has been produced.");
+    } else {
+        println!("Packages:");
+        for x in packages {
+            println!("  {}", x.display());
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,350 @@
+//! Environment and working directory of the launched executable.
+//!
+//! ```yaml
+//! run:
+//!   env:
+//!     - ASAN_OPTIONS=detect_leaks=0
+//!     - LOG_LEVEL=debug
+//!   unset:
+//!     - LD_PRELOAD
+//!   env_file: .env
+//!   cwd: data
+//! ```
+//!
+//! Variables are applied in the following order (last has the highest priority):
+//! * baldr's own environment
+//! * `env_file` (relative to the project directory)
+//! * `env`
+//! * `--env KEY=VALUE` on the command line
+//!
+//! Variables in `unset` are removed from baldr's environment, but can be set again by the
+//! sources above. Sanitizer runtime options (e.g. `ASAN_OPTIONS`) are merged option by option
+//! instead of being replaced.
+//!
+//! Named run configurations under `runs` are launched by `baldr run <name>`. Besides the keys
+//! of `run` (environment, working directory, timeout and limits), each of them can name the
+//! target, its arguments, a debugger or a wrapper command. Arguments and wrappers are split on
+//! whitespace, a list keeps arguments containing spaces intact. The environment of a named run is
+//! layered on top of the one of `run`, and the other keys of `run` apply unless they are given:
+//!
+//! ```yaml
+//! runs:
+//!   server:
+//!     target: app
+//!     args: --port 8080 --verbose
+//!     env:
+//!       - LOG_LEVEL=trace
+//!     cwd: data
+//!   dev:
+//!     target: app
+//!     args: [--config, dev config.toml]
+//!   leaks:
+//!     target: app
+//!     wrapper: valgrind --leak-check=full
+//! ```
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{self, Path};
+use std::process::Command;
+
+use config::Config;
+
+use crate::{
+    get_command,
+    get_list,
+};
+
+/// How the built executable is launched, either by the `run` section or a named run
+/// configuration.
+#[derive(Debug, PartialEq)]
+pub struct Profile {
+    /// Section of the configuration holding the environment, working directory, timeout and
+    /// limits, e.g. `run` or `runs.server`
+    pub section: String,
+
+    /// Target to build and run, if it is not given on the command line
+    pub target: Option<String>,
+
+    /// Arguments preceding the ones given on the command line
+    pub args: Vec<String>,
+
+    /// Debugger to run the executable under, instead of the configured `debugger`
+    pub debugger: Option<String>,
+
+    /// Command the executable is run by, e.g. `valgrind --leak-check=full`
+    pub wrapper: Vec<String>,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            section: "run".into(),
+            target: None,
+            args: Vec::new(),
+            debugger: None,
+            wrapper: Vec::new(),
+        }
+    }
+}
+
+/// Read the named run configuration `runs.<name>`, its target defaults to the name.
+///
+/// # Errors
+///
+/// Returns an error listing the available names if there is no such run configuration.
+pub fn get_profile(cfg: &Config, name: &str) -> Result<Profile, String> {
+    // Keys are case-insensitive.
+    let name = name.to_lowercase();
+    let runs = cfg.get_table("runs").unwrap_or_default();
+
+    if !runs.contains_key(&name) {
+        let mut names = runs.keys().map(String::as_str).collect::<Vec<_>>();
+        names.sort_unstable();
+
+        return Err(if names.is_empty() {
+            format!("No run configuration `{name}`, there are none under `runs`")
+        } else {
+            format!("No run configuration `{name}`, available ones: {}", names.join(", "))
+        });
+    }
+
+    let section = format!("runs.{name}");
+
+    Ok(Profile {
+        target: Some(cfg.get_string(&format!("{section}.target")).unwrap_or_else(|_| name.clone())),
+        args: get_command(cfg, &format!("{section}.args")),
+        debugger: cfg.get_string(&format!("{section}.debugger")).ok(),
+        wrapper: get_command(cfg, &format!("{section}.wrapper")),
+        section,
+    })
+}
+
+/// Sections of the configuration applying to a run configuration, in the order of priority (last
+/// has the highest), e.g. `run` and `runs.server`.
+pub fn sections(section: &str) -> Vec<&str> {
+    if section == "run" { vec!["run"] } else { vec!["run", section] }
+}
+
+/// Read a key of the section, falling back to the one of `run` for named run configurations.
+pub fn get_string(cfg: &Config, section: &str, key: &str) -> Option<String> {
+    sections(section).iter().rev().find_map(|x| cfg.get_string(&format!("{x}.{key}")).ok())
+}
+
+/// Whether the variable holds sanitizer runtime options, i.e. a colon separated list of
+/// `key=value` pairs.
+fn is_sanitizer_options(key: &str) -> bool {
+    ["ASAN", "LSAN", "MSAN", "TSAN", "UBSAN", "HWASAN"].iter()
+        .any(|x| key.strip_prefix(x) == Some("_OPTIONS"))
+}
+
+/// Merge sanitizer runtime options, options in `overlay` win.
+///
+/// ```
+/// use baldr::launch::merge_sanitizer_options;
+///
+/// assert_eq!(
+///     merge_sanitizer_options("detect_leaks=1:halt_on_error=1", "detect_leaks=0"),
+///     "detect_leaks=0:halt_on_error=1"
+/// );
+/// ```
+pub fn merge_sanitizer_options(base: &str, overlay: &str) -> String {
+    let mut options: Vec<(&str, &str)> = Vec::new();
+
+    for option in base.split(':').chain(overlay.split(':')).filter(|x| !x.is_empty()) {
+        let (key, value) = option.split_once('=').unwrap_or((option, ""));
+        match options.iter_mut().find(|(k, _)| *k == key) {
+            Some(existing) => existing.1 = value,
+            None => options.push((key, value)),
+        }
+    }
+
+    options.iter()
+        .map(|(k, v)| format!("{k}={v}"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Parse the content of a `.env` file.
+///
+/// Empty lines and comments are skipped, `export` prefixes and quotes around values are removed.
+pub fn parse_env_file(content: &str) -> Vec<(String, String)> {
+    content.lines()
+        .map(str::trim)
+        .filter(|x| !x.is_empty() && !x.starts_with('#'))
+        .filter_map(|x| {
+            let line = x.strip_prefix("export ").unwrap_or(x);
+            let (key, value) = line.split_once('=')?;
+            let value = value.trim();
+            let value = value.strip_prefix('"').and_then(|x| x.strip_suffix('"'))
+                .or_else(|| value.strip_prefix('\'').and_then(|x| x.strip_suffix('\'')))
+                .unwrap_or(value);
+
+            Some((key.trim().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Parse `KEY=VALUE` pairs.
+///
+/// # Errors
+///
+/// Returns an error if the pair does not contain `=`.
+pub fn parse_env_pair(pair: &str) -> Result<(String, String), String> {
+    pair.split_once('=')
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .ok_or_else(|| format!("Invalid environment variable `{pair}`, expected `KEY=VALUE`"))
+}
+
+/// Add `KEY=VALUE` pairs to the variables, merging sanitizer options.
+fn add_env(vars: &mut BTreeMap<String, String>, pairs: &[String]) -> Result<(), String> {
+    for pair in pairs {
+        let (key, value) = parse_env_pair(pair)?;
+        let value = match vars.get(&key) {
+            Some(base) if is_sanitizer_options(&key) => merge_sanitizer_options(base, &value),
+            _ => value,
+        };
+        vars.insert(key, value);
+    }
+
+    Ok(())
+}
+
+/// Apply the environment and working directory configured under `section` to the command.
+///
+/// A named run configuration is applied on top of `run`, its working directory replaces the one
+/// of `run`.
+///
+/// # Errors
+///
+/// Returns an error if the `.env` file cannot be read or a variable is malformed.
+pub fn apply(cmd: &mut Command, project: &str, cfg: &Config, section: &str, cli_env: &[String]) -> Result<(), String> {
+    let mut vars: BTreeMap<String, String> = BTreeMap::new();
+    let mut unset: Vec<String> = Vec::new();
+
+    for section in sections(section) {
+        if let Ok(file) = cfg.get_string(&format!("{section}.env_file")) {
+            let file = Path::new(project).join(file);
+            let content = fs::read_to_string(&file)
+                .map_err(|e| format!("Failed to read env file `{}`: {e}", file.display()))?;
+            vars.extend(parse_env_file(&content));
+        }
+
+        add_env(&mut vars, &get_list(cfg, &format!("{section}.env")))?;
+        unset.extend(get_list(cfg, &format!("{section}.unset")));
+    }
+
+    add_env(&mut vars, cli_env)?;
+
+    for key in unset {
+        if !vars.contains_key(&key) {
+            cmd.env_remove(key);
+        }
+    }
+
+    for (key, value) in vars {
+        let value = match env::var(&key) {
+            Ok(base) if is_sanitizer_options(&key) => merge_sanitizer_options(&base, &value),
+            _ => value,
+        };
+        cmd.env(key, value);
+    }
+
+    if let Some(cwd) = get_string(cfg, section, "cwd") {
+        let cwd = path::absolute(Path::new(project).join(cwd))
+            .map_err(|e| format!("Invalid working directory: {e}"))?;
+        cmd.current_dir(cwd);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_file() {
+        let content = "# comment\n\nexport A=1\nB=\"two words\"\nC='3'\ninvalid\n";
+
+        assert_eq!(
+            parse_env_file(content),
+            vec![
+                ("A".to_string(), "1".to_string()),
+                ("B".to_string(), "two words".to_string()),
+                ("C".to_string(), "3".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn apply_env() {
+        let cfg = Config::builder()
+            .set_override("run.env", vec!["A=1", "UBSAN_OPTIONS=print_stacktrace=1"]).unwrap()
+            .set_override("run.unset", vec!["HOME", "A"]).unwrap()
+            .set_override("run.cwd", "data").unwrap()
+            .build().unwrap();
+
+        let mut cmd = Command::new("true");
+        apply(&mut cmd, "/project", &cfg, "run", &["A=2".into(), "UBSAN_OPTIONS=halt_on_error=1".into()]).unwrap();
+
+        let envs: Vec<_> = cmd.get_envs().collect();
+        assert!(envs.contains(&("HOME".as_ref(), None)));
+        assert!(envs.contains(&("A".as_ref(), Some("2".as_ref()))));
+        assert!(envs.contains(&("UBSAN_OPTIONS".as_ref(), Some("print_stacktrace=1:halt_on_error=1".as_ref()))));
+        assert_eq!(cmd.get_current_dir(), Some(Path::new("/project/data")));
+    }
+
+    #[test]
+    fn apply_named_env() {
+        let cfg = Config::builder()
+            .set_override("run.env", vec!["A=1", "B=1"]).unwrap()
+            .set_override("run.cwd", "data").unwrap()
+            .set_override("runs.server.env", vec!["B=2"]).unwrap()
+            .set_override("runs.server.cwd", "server").unwrap()
+            .build().unwrap();
+
+        let mut cmd = Command::new("true");
+        apply(&mut cmd, "/project", &cfg, "runs.server", &[]).unwrap();
+
+        let envs: Vec<_> = cmd.get_envs().collect();
+        assert!(envs.contains(&("A".as_ref(), Some("1".as_ref()))));
+        assert!(envs.contains(&("B".as_ref(), Some("2".as_ref()))));
+        assert_eq!(cmd.get_current_dir(), Some(Path::new("/project/server")));
+
+        assert_eq!(get_string(&cfg, "runs.server", "cwd").as_deref(), Some("server"));
+        assert_eq!(get_string(&cfg, "runs.client", "cwd").as_deref(), Some("data"));
+        assert_eq!(get_string(&cfg, "runs.server", "timeout"), None);
+    }
+
+    #[test]
+    fn named_runs() {
+        let cfg = Config::builder()
+            .set_override("runs.server.target", "app").unwrap()
+            .set_override("runs.server.args", "--port 8080").unwrap()
+            .set_override("runs.leaks.wrapper", vec!["valgrind", "--leak-check=full"]).unwrap()
+            .set_override("runs.dev.args", vec!["--config", "dev config.toml"]).unwrap()
+            .build().unwrap();
+
+        assert_eq!(
+            get_profile(&cfg, "Server").unwrap(),
+            Profile {
+                section: "runs.server".into(),
+                target: Some("app".into()),
+                args: vec!["--port".into(), "8080".into()],
+                debugger: None,
+                wrapper: Vec::new(),
+            }
+        );
+
+        let leaks = get_profile(&cfg, "leaks").unwrap();
+        assert_eq!(leaks.target.as_deref(), Some("leaks"));
+        assert_eq!(leaks.wrapper, ["valgrind", "--leak-check=full"]);
+
+        assert_eq!(get_profile(&cfg, "dev").unwrap().args, ["--config", "dev config.toml"]);
+
+        assert_eq!(get_profile(&cfg, "client").unwrap_err(), "No run configuration `client`, available ones: dev, leaks, server");
+        assert!(get_profile(&Config::default(), "client").is_err());
+    }
+}
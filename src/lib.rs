@@ -1,23 +1,45 @@
 use std::env;
+use std::fs;
 use std::io::{self, BufRead, Write};
-use std::path::{Path,PathBuf};
+use std::path::{self, Path, PathBuf};
+use std::os::unix::process::ExitStatusExt;
 use std::process::{ExitStatus, Command};
 
-use config::builder::DefaultState;
-use config::{Config, ConfigBuilder};
+use config::Config;
 use walkdir::WalkDir;
 
 use log::*;
 
+pub mod bench;
+pub mod cmake_cache;
+pub mod coverage;
+pub mod deps;
+pub mod dirs;
+pub mod formatting;
+pub mod git;
+pub mod hooks;
+pub mod init;
+pub mod install;
+pub mod launch;
+pub mod logs;
+pub mod process;
+pub mod reconfigure;
+pub mod settings;
+pub mod tidy;
+pub mod workspace;
+
 /// Baldur, a C++/CMake project builder.
 ///
 /// Build, run and debug.
 ///
 /// Additional configuration is done via config files. Lookup in order (last has the highest
 /// priority):
-/// * XDG_CONFIG_HOME
-/// * HOME
-/// * Project directory
+/// * $XDG_CONFIG_HOME/baldr/config (XDG_CONFIG_HOME defaults to ~/.config)
+/// * Project directory (looked up from the current directory if `--project` is not given)
+/// * .baldr.local in the project directory for personal overrides
+///
+/// The legacy $XDG_CONFIG_HOME/.baldr and $HOME/.baldr files (the latter overriding the former) are
+/// still read with the lowest priority, but they are deprecated.
 ///
 /// If multiple files found, they are merged. In case of keys defined in multiple places, the
 /// highest priority will be kept.
@@ -33,19 +55,20 @@ use log::*;
 /// * INI
 /// * RON
 /// * JSON5
-#[derive(clap::Parser)]
+#[derive(Clone, clap::Parser)]
 #[command(version, verbatim_doc_comment)]
 pub struct Args {
-    /// Project path to build (containing the root CMakeLists.txt)
-    #[arg(short, long)]
-    pub project: String,
+    /// Project path to build (containing the root CMakeLists.txt), looked up from the current
+    /// directory if not given
+    #[arg(short, long, global = true)]
+    pub project: Option<String>,
 
     /// Build type
-    #[arg(short, long, default_value_t = String::from("Debug"))]
+    #[arg(short, long, global = true, default_value_t = String::from("Debug"))]
     pub build_type: String,
 
     /// Overriding build directory
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub build_dir: Option<String>,
 
     /// CMake target to build
@@ -57,23 +80,28 @@ pub struct Args {
     pub delete: bool,
 
     /// Skip confirmations (can be handy for scripting)
-    #[arg(long, default_value_t = false)]
+    #[arg(long, global = true, default_value_t = false)]
     pub no_confirm: bool,
 
-    /// Skip CMake configure (only applicable if it is already configured)
+    /// Skip CMake configure even if its inputs have changed (only applicable if it is already
+    /// configured)
     #[arg(long, default_value_t = false)]
     pub no_configure: bool,
 
-    /// Number of parallel build jobs
-    #[arg(short, long, default_value_t = 1)]
-    pub jobs: u8,
+    /// Number of parallel build jobs [default: `build.jobs` or the available parallelism]
+    #[arg(short, long, global = true, value_parser = clap::value_parser!(u32).range(1..))]
+    pub jobs: Option<u32>,
+
+    /// Do not start new build jobs if the load average is above this (Make and Ninja)
+    #[arg(short, long, global = true, value_name = "LOAD", value_parser = parse_load_average)]
+    pub load_average: Option<f64>,
 
     /// Arguments to forward to CMake
     #[arg(short = 'D', long = "cmake-define")]
     pub cmake_args: Vec<String>,
 
     /// Overriding configuration file
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub config: Option<String>,
 
     /// Run the built binary
@@ -84,64 +112,476 @@ pub struct Args {
     #[arg(long)]
     pub debug: bool,
 
+    /// Stop the executable if it runs longer than this (e.g. `30s`, `5m`)
+    #[arg(long, value_name = "DURATION")]
+    pub timeout: Option<String>,
+
+    /// Run the built binary this many times, and report statistics of the wall time and peak RSS
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub bench: Option<u32>,
+
+    /// Environment variables for the executable (can be given multiple times)
+    #[arg(long = "env", value_name = "KEY=VALUE")]
+    pub env: Vec<String>,
+
+    /// Build with coverage instrumentation in a dedicated build directory, and report the coverage
+    /// after running
+    #[arg(long, default_value_t = false)]
+    pub coverage: bool,
+
+    /// Print the commands that would be run without executing anything
+    #[arg(long, global = true, default_value_t = false)]
+    pub dry_run: bool,
+
     /// Arguments to be forwarded to the executable
     #[arg(last = true)]
     pub exe_args: Vec<String>,
+
+    /// Cache variables to remove when configuring, see `baldr cache unset`
+    #[arg(skip)]
+    pub cmake_unset: Vec<String>,
+
+    /// Prefixes prepended to `CMAKE_PREFIX_PATH` when configuring, see
+    /// [`workspace::add_prefix_paths`]
+    #[arg(skip)]
+    pub prefix_paths: Vec<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+}
+
+#[derive(Clone, clap::Subcommand)]
+pub enum Commands {
+    /// List the build directories of the project, or prune them
+    Dirs {
+        /// Delete the build directories selected by `--older-than` and `--match`
+        #[arg(long, default_value_t = false)]
+        prune: bool,
+
+        /// Select build directories not built for at least this long (e.g. `30d`)
+        #[arg(long, value_name = "DURATION")]
+        older_than: Option<String>,
+
+        /// Select build directories whose name matches the pattern (`*` and `?` wildcards)
+        #[arg(long = "match", value_name = "PATTERN")]
+        pattern: Option<String>,
+    },
+
+    /// Point `compile_commands.json` to another build directory without rebuilding
+    Use {
+        /// Name of a build directory under `<project>/build` (e.g. `release-clang++`), a unique
+        /// prefix of it (e.g. `release-clang`) or a path [default: the build directory of the build
+        /// options, e.g. `--build-type Release`]
+        dir: Option<String>,
+    },
+
+    /// Run `clang-tidy` in parallel over the translation units of the compilation database
+    Tidy {
+        /// Only check files changed since this git ref (e.g. `origin/main`)
+        #[arg(long, value_name = "REF")]
+        since: Option<String>,
+
+        /// Apply the suggested fixes
+        #[arg(long, default_value_t = false)]
+        fix: bool,
+    },
+
+    /// Install the artifacts of the build directory with `cmake --install`
+    Install {
+        /// Installation prefix
+        #[arg(long, value_name = "DIR")]
+        prefix: String,
+
+        /// Only install this component
+        #[arg(long)]
+        component: Option<String>,
+    },
+
+    /// Package the build directory with `cpack`, with the generators given in the configuration
+    Package,
+
+    /// Report the coverage of the coverage build from the data of previous runs (e.g. tests)
+    Coverage,
+
+    /// Write a commented configuration file based on the tools found and the project
+    Init {
+        /// Write the global configuration of the user instead of the project's
+        #[arg(long, default_value_t = false)]
+        global: bool,
+    },
+
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+
+    /// List the logs of configuring, building and running, or show the most recent one
+    Log {
+        /// Show the most recent log
+        #[arg(long, default_value_t = false)]
+        last: bool,
+
+        /// Show the most recent log of a failed invocation
+        #[arg(long, default_value_t = false, conflicts_with = "last")]
+        failed: bool,
+    },
+
+    /// Format the C/C++ sources of the project with `clang-format`
+    Format {
+        /// Do not modify the files, but show the violations as a diff and fail if there is any
+        #[arg(long, default_value_t = false)]
+        check: bool,
+
+        /// Only format files changed since this git ref (e.g. `origin/main`)
+        #[arg(long, value_name = "REF")]
+        since: Option<String>,
+    },
+
+    /// Inspect or edit the CMake cache of the build directory
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommands,
+    },
+
+    /// Build and run a named run configuration from `runs`
+    Run {
+        /// Name of the run configuration
+        name: String,
+
+        /// Arguments appended to the configured ones
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+
+    /// Build the member projects of the workspace in dependency order
+    Workspace {
+        #[command(subcommand)]
+        command: WorkspaceCommands,
+    },
+}
+
+#[derive(Clone, clap::Subcommand)]
+pub enum WorkspaceCommands {
+    /// Configure, build and install each member into the staging prefix
+    Build,
 }
 
-fn read_one_config(var: &str, cfg: ConfigBuilder<DefaultState>) -> ConfigBuilder<DefaultState> {
-    if let Ok(x) = env::var(var) {
-        log::debug!("Looking for config in {var}.");
+#[derive(Clone, clap::Subcommand)]
+pub enum CacheCommands {
+    /// Show a variable with its type and help string
+    Get {
+        name: String,
+    },
+
+    /// Set a variable and reconfigure
+    Set {
+        name: String,
+        value: String,
+
+        /// Type of the variable (e.g. `BOOL`, `PATH`) [default: its current type or `STRING`]
+        #[arg(long = "type", value_name = "TYPE")]
+        kind: Option<String>,
+    },
 
-        let config_path = Path::new(&x).join(".baldr");
+    /// Remove a variable and reconfigure
+    Unset {
+        name: String,
+    },
 
-        cfg.add_source(
-            config::File::with_name(config_path.to_str().expect("Non UTF-8 string in path")
-        ).required(false))
+    /// List the variables with their types and help strings
+    List {
+        /// Only list the variables whose name contains the text (case-insensitive)
+        #[arg(long, value_name = "TEXT")]
+        filter: Option<String>,
+
+        /// Also list internal variables
+        #[arg(long, default_value_t = false)]
+        all: bool,
+    },
+}
+
+#[derive(Clone, clap::Subcommand)]
+pub enum ConfigCommands {
+    /// Print the merged configuration
+    Show,
+
+    /// List the sources setting the key from the lowest priority to the highest
+    Explain {
+        /// Key of the configuration, e.g. `compiler.cxx`
+        key: String,
+    },
+}
+
+impl Args {
+    /// Number of parallel jobs, see [`Args::resolve_parallelism`].
+    pub fn jobs(&self) -> u32 {
+        self.jobs.unwrap_or(1)
     }
-    else {
-        log::debug!("{var} is not defined.");
-        cfg
+
+    /// Decide the number of parallel jobs and the load average limit if they are not given.
+    ///
+    /// Jobs are taken from `build.jobs` or the available parallelism, capped by the available
+    /// memory if `build.memory_per_job` is set. The load average is taken from
+    /// `build.load_average`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the configured values are malformed.
+    pub fn resolve_parallelism(&mut self, cfg: &Config) -> Result<(), String> {
+        if self.load_average.is_none() {
+            self.load_average = match cfg.get_string("build.load_average") {
+                Ok(x) => Some(parse_load_average(&x).map_err(|e| format!("{e} in `build.load_average`"))?),
+                Err(_) => None,
+            };
+        }
+
+        if self.jobs.is_some() {
+            return Ok(());
+        }
+
+        let (mut jobs, source) = match cfg.get_int("build.jobs") {
+            Ok(x) => (
+                u32::try_from(x).ok().filter(|x| *x > 0).ok_or_else(|| format!("Invalid number of jobs in `build.jobs`: {x}"))?,
+                "configuration",
+            ),
+            Err(_) => (
+                std::thread::available_parallelism().map_or(1, |x| u32::try_from(x.get()).unwrap_or(u32::MAX)),
+                "available parallelism",
+            ),
+        };
+
+        if let Ok(x) = cfg.get_string("build.memory_per_job") {
+            let per_job = process::parse_size(&x)?.max(1);
+
+            match process::available_memory() {
+                Some(available) => {
+                    let cap = u32::try_from(available / per_job).unwrap_or(u32::MAX).max(1);
+                    if cap < jobs {
+                        info!("Limiting jobs from {jobs} to {cap} as {} memory is available.", format_size(available));
+                        jobs = cap;
+                    }
+                },
+                None => warn!("Available memory is unknown, `build.memory_per_job` is ignored."),
+            }
+        }
+
+        info!("Using {jobs} parallel job(s) based on the {source}.");
+        self.jobs = Some(jobs);
+        Ok(())
+    }
+
+    /// Find the project root from the current directory if it is not given, see
+    /// [`find_project_root`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the project root cannot be found.
+    pub fn resolve_project(&mut self) -> Result<(), String> {
+        if self.project.is_some() {
+            return Ok(());
+        }
+
+        let root = lookup_project()?
+            .ok_or("No project is found in the current directory or its parents, give it with `--project`")?;
+
+        info!("Using project: {}", root.display());
+        self.project = Some(root.to_string_lossy().into_owned());
+        Ok(())
+    }
+
+    /// Path of the project.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the project is neither given nor resolved, see [`Args::resolve_project`].
+    pub fn project(&self) -> &str {
+        self.project.as_deref().expect("Project is resolved")
     }
 }
 
-/// Read configuration from environment variables and files.
-///
-/// Files are looked in the following directories:
-/// * XDG_CONFIG_HOME
-/// * HOME
-/// * Project directory
+/// Whether the directory has a file marking the project root, e.g. `.baldr.yaml`.
+fn has_root_marker(dir: &Path) -> bool {
+    fs::read_dir(dir).is_ok_and(|entries| {
+        entries
+            .filter_map(|e| e.ok())
+            .any(|e| e.file_name().to_string_lossy().starts_with(".baldr.") && e.path().is_file())
+    })
+}
+
+/// Whether the directory has a `CMakeLists.txt` declaring a project.
+fn has_cmake_project(dir: &Path) -> bool {
+    fs::read_to_string(dir.join("CMakeLists.txt")).is_ok_and(|content| {
+        content.lines()
+            .map(|x| x.trim_start().to_lowercase())
+            .any(|x| !x.starts_with('#') && x.strip_prefix("project").is_some_and(|x| x.trim_start().starts_with('(')))
+    })
+}
+
+/// Find the project root walking up from the current directory, see [`find_project_root`].
 ///
 /// # Errors
 ///
-/// Returns an error if config files exist but cannot be read or the configuration is invalid.
+/// Returns an error if the current directory is not accessible.
+pub fn lookup_project() -> Result<Option<PathBuf>, String> {
+    let cwd = env::current_dir().map_err(|e| format!("Failed to get the current directory: {e}"))?;
+    let home = env::var_os("HOME").map(PathBuf::from);
+
+    Ok(find_project_root(&cwd, home.as_deref()))
+}
+
+/// Find the project root walking up from `start`.
 ///
-/// # Panics
+/// The nearest directory with a `.baldr.*` file marks the root, except for the home directory,
+/// where it is the user's configuration. Otherwise, the outermost directory with a
+/// `CMakeLists.txt` declaring a `project()` is the root.
+pub fn find_project_root(start: &Path, home: Option<&Path>) -> Option<PathBuf> {
+    let mut outermost = None;
+
+    for dir in start.ancestors() {
+        if home != Some(dir) && has_root_marker(dir) {
+            return Some(dir.to_path_buf());
+        }
+
+        if has_cmake_project(dir) {
+            outermost = Some(dir.to_path_buf());
+        }
+    }
+
+    outermost
+}
+
+/// A source of the configuration, see [`config_layers`].
+pub struct Layer {
+    /// Where the values come from, e.g. `HOME (/home/user/.baldr.yaml)`
+    pub label: String,
+    pub config: Config,
+}
+
+/// Extensions of the configuration files, as recognized by the `config` crate.
+const CONFIG_EXTENSIONS: [&str; 7] = ["toml", "json", "yaml", "yml", "ini", "ron", "json5"];
+
+/// Find the configuration file with the given base name, e.g. `.baldr.yaml` for `.baldr`.
+fn find_config_file(base: &Path) -> Option<PathBuf> {
+    CONFIG_EXTENSIONS.iter()
+        .map(|x| {
+            let mut file = base.as_os_str().to_os_string();
+            file.push(format!(".{x}"));
+            PathBuf::from(file)
+        })
+        .find(|x| x.is_file())
+}
+
+fn file_layer(label: &str, base: &Path) -> Result<Option<Layer>, config::ConfigError> {
+    let Some(file) = find_config_file(base) else {
+        debug!("No config is found in {label}.");
+        return Ok(None);
+    };
+
+    debug!("Reading config from `{}`.", file.display());
+
+    Ok(Some(Layer {
+        label: format!("{label} ({})", file.display()),
+        config: Config::builder().add_source(config::File::from(file.as_path())).build()?,
+    }))
+}
+
+/// Directory of the global configuration, i.e. `$XDG_CONFIG_HOME/baldr`.
 ///
-/// Panics if the project path is not a valid UTF-8 string.
-pub fn read_config(config_override: &Option<String>, project: &String) -> Result<Config, config::ConfigError> {
-    let mut config = Config::builder();
+/// `XDG_CONFIG_HOME` defaults to `$HOME/.config`, and it is ignored if it is not absolute, as the
+/// XDG Base Directory Specification requires.
+pub fn global_config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|x| x.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|x| PathBuf::from(x).join(".config")))
+        .map(|x| x.join("baldr"))
+}
 
-    config = if let Some(x) = config_override {
-        config.add_source(config::File::with_name(x.as_str()))
-    } else {
-        config = read_one_config("XDG_CONFIG_HOME", config);
-        config = read_one_config("HOME", config);
-        config.add_source(
-            config::File::with_name(
-                Path::new(project).join(".baldr")
-                    .to_str()
-                    .expect("Non UTF-8 string in path")
-            )
-            .required(false)
-        )
+/// Layer of a config file in a legacy location, which is still read but deprecated.
+fn legacy_layer(var: &str, xdg: Option<&Path>) -> Result<Option<Layer>, config::ConfigError> {
+    let Ok(dir) = env::var(var) else {
+        debug!("{var} is not defined.");
+        return Ok(None);
     };
 
-    config
-        .add_source(config::Environment::with_prefix("BALDR"))
+    let base = Path::new(&dir).join(".baldr");
+    if let Some(file) = find_config_file(&base) {
+        warn!(
+            "Config file `{}` is deprecated, move it to `{}`.",
+            file.display(),
+            xdg.map(|x| x.join("config.yaml").display().to_string()).unwrap_or_default()
+        );
+    }
+
+    file_layer(&format!("{var} (deprecated)"), &base)
+}
+
+/// Read the sources of the configuration separately, from the lowest priority to the highest.
+///
+/// Files are looked up in the following order:
+/// * `$XDG_CONFIG_HOME/.baldr.*` and `$HOME/.baldr.*` (deprecated)
+/// * `$XDG_CONFIG_HOME/baldr/config.*`, see [`global_config_dir`]
+/// * `<project>/.baldr.*` (the project root if `--project` is not given, see [`find_project_root`])
+/// * `<project>/.baldr.local.*` for personal overrides, not meant to be committed
+///
+/// Variables with the `BALDR_` prefix have the highest priority. Files which do not exist are not
+/// listed.
+///
+/// # Errors
+///
+/// Returns an error if config files exist but cannot be read or parsed.
+pub fn config_layers(config_override: &Option<String>, project: &str) -> Result<Vec<Layer>, config::ConfigError> {
+    let mut layers = Vec::new();
+
+    if let Some(x) = config_override {
+        layers.push(Layer {
+            label: format!("--config ({x})"),
+            config: Config::builder().add_source(config::File::with_name(x.as_str())).build()?,
+        });
+    } else {
+        let global = global_config_dir();
+
+        // `$HOME/.baldr` overrides `$XDG_CONFIG_HOME/.baldr`, as it always has.
+        layers.extend(legacy_layer("XDG_CONFIG_HOME", global.as_deref())?);
+        layers.extend(legacy_layer("HOME", global.as_deref())?);
+
+        if let Some(dir) = &global {
+            layers.extend(file_layer("global", &dir.join("config"))?);
+        }
+
+        layers.extend(file_layer("project", &Path::new(project).join(".baldr"))?);
+        layers.extend(file_layer("local", &Path::new(project).join(".baldr.local"))?);
+    }
+
+    layers.push(Layer {
+        label: "environment (BALDR_*)".into(),
+        config: Config::builder().add_source(config::Environment::with_prefix("BALDR")).build()?,
+    });
+
+    Ok(layers)
+}
+
+/// Merge the layers, later ones overriding the earlier ones.
+///
+/// # Errors
+///
+/// Returns an error if the merged configuration is invalid.
+pub fn merge_layers(layers: &[Layer]) -> Result<Config, config::ConfigError> {
+    layers.iter()
+        .fold(Config::builder(), |builder, layer| builder.add_source(layer.config.clone()))
         .build()
 }
 
+/// Read configuration from environment variables and files, see [`config_layers`].
+///
+/// # Errors
+///
+/// Returns an error if config files exist but cannot be read or the configuration is invalid.
+pub fn read_config(config_override: &Option<String>, project: &str) -> Result<Config, config::ConfigError> {
+    merge_layers(&config_layers(config_override, project)?)
+}
+
 pub fn get_cc(cfg: &Config) -> String {
     cfg.get_string("compiler.cc").unwrap_or_default()
 }
@@ -150,8 +590,9 @@ pub fn get_cxx(cfg: &Config) -> String {
     cfg.get_string("compiler.cxx").unwrap_or_default()
 }
 
-pub fn get_cmake_definitions(cfg: &Config) -> Vec<String> {
-    match cfg.get_array("cmake.definitions") {
+/// Read a list of strings, silently dropping other kind of elements.
+pub fn get_list(cfg: &Config, key: &str) -> Vec<String> {
+    match cfg.get_array(key) {
         Ok(x) =>
             x.iter()
                 .filter_map(|x| x.clone().into_string().ok())
@@ -160,12 +601,133 @@ pub fn get_cmake_definitions(cfg: &Config) -> Vec<String> {
     }
 }
 
-/// Invoke CMake's configure command.
+pub fn get_cmake_definitions(cfg: &Config) -> Vec<String> {
+    get_list(cfg, "cmake.definitions")
+}
+
+/// Cross-compilation settings.
+///
+/// Either an existing CMake toolchain file is used or one is generated into the build directory
+/// from the target triple and sysroot.
+///
+/// ```yaml
+/// toolchain:
+///   file: cmake/aarch64.cmake
+///   # or inline
+///   target: aarch64-linux-gnu
+///   sysroot: /usr/aarch64-linux-gnu
+///   # command to launch the built executable with
+///   emulator: qemu-aarch64 -L /usr/aarch64-linux-gnu
+/// ```
+#[derive(Debug, Default, PartialEq)]
+pub struct Toolchain {
+    pub file: Option<String>,
+    pub target: Option<String>,
+    pub sysroot: Option<String>,
+    pub emulator: Vec<String>,
+}
+
+impl Toolchain {
+    /// Name of the toolchain used in the build directory name.
+    ///
+    /// The target triple if it is given, otherwise the file name of the toolchain file without
+    /// its extension.
+    pub fn name(&self) -> Option<String> {
+        self.target.clone().or_else(|| {
+            self.file.as_ref()
+                .and_then(|x| Path::new(x).file_stem())
+                .map(|x| x.to_string_lossy().into_owned())
+        })
+    }
+
+    /// Path of the toolchain file given to CMake.
+    ///
+    /// Relative paths are resolved from the project directory, while inline toolchains are
+    /// generated into the build directory.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current working directory is not accessible.
+    pub fn path(&self, project: &str, build_dir: &Path) -> PathBuf {
+        let path = match &self.file {
+            Some(file) => Path::new(project).join(file),
+            None => build_dir.join("baldr-toolchain.cmake"),
+        };
+
+        path::absolute(path).expect("Cannot access current working directory")
+    }
+
+    /// Content of the generated toolchain file for inline toolchains.
+    pub fn content(&self) -> Option<String> {
+        if self.file.is_some() {
+            return None;
+        }
+
+        let target = self.target.as_ref()?;
+        let system = if target.contains("linux") {
+            "Linux"
+        } else if target.contains("windows") || target.contains("mingw") {
+            "Windows"
+        } else if target.contains("apple") || target.contains("darwin") {
+            "Darwin"
+        } else {
+            "Generic"
+        };
+
+        let mut content = format!(
+            "set(CMAKE_SYSTEM_NAME {system})\n\
+             set(CMAKE_SYSTEM_PROCESSOR {})\n\
+             set(CMAKE_C_COMPILER_TARGET {target})\n\
+             set(CMAKE_CXX_COMPILER_TARGET {target})\n",
+            target.split('-').next().unwrap_or(target),
+        );
+
+        if let Some(sysroot) = &self.sysroot {
+            content += &format!(
+                "set(CMAKE_SYSROOT {sysroot})\n\
+                 set(CMAKE_FIND_ROOT_PATH_MODE_PROGRAM NEVER)\n\
+                 set(CMAKE_FIND_ROOT_PATH_MODE_LIBRARY ONLY)\n\
+                 set(CMAKE_FIND_ROOT_PATH_MODE_INCLUDE ONLY)\n\
+                 set(CMAKE_FIND_ROOT_PATH_MODE_PACKAGE ONLY)\n"
+            );
+        }
+
+        Some(content)
+    }
+}
+
+/// Read the toolchain from the configuration.
+///
+/// Returns `None` if neither a toolchain file nor a target triple is configured.
+pub fn get_toolchain(cfg: &Config) -> Option<Toolchain> {
+    let toolchain = Toolchain {
+        file: cfg.get_string("toolchain.file").ok(),
+        target: cfg.get_string("toolchain.target").ok(),
+        sysroot: cfg.get_string("toolchain.sysroot").ok(),
+        emulator: get_command(cfg, "toolchain.emulator"),
+    };
+
+    if toolchain.file.is_none() && toolchain.target.is_none() {
+        None
+    } else {
+        Some(toolchain)
+    }
+}
+
+/// Read a command given either as a list or as a whitespace separated string.
+pub fn get_command(cfg: &Config, key: &str) -> Vec<String> {
+    match cfg.get_string(key) {
+        Ok(x) => x.split_whitespace().map(String::from).collect(),
+        Err(_) => get_list(cfg, key),
+    }
+}
+
+/// Assemble CMake's configure command.
 ///
 /// # Errors
 ///
-/// Returns an error, if the process cannot be started.
-pub fn configure(path: &Path, args: &Args, config: &Config) -> Result<ExitStatus, String> {
+/// Returns an error if the dependencies are misconfigured, see [`deps::cmake_definitions`].
+pub fn configure_cmd(path: &Path, args: &Args, config: &Config) -> Result<Command, String> {
     let mut cmd = Command::new("cmake");
 
     let cc: String = get_cc(config);
@@ -176,44 +738,195 @@ pub fn configure(path: &Path, args: &Args, config: &Config) -> Result<ExitStatus
     }
 
     cmd.args([
-        "-S", args.project.as_str(),
+        "-S", args.project(),
         "-B", &path.to_string_lossy(),
         format!("-DCMAKE_BUILD_TYPE={}", args.build_type).as_str(),
         "-DCMAKE_EXPORT_COMPILE_COMMANDS=ON"
     ]);
 
-    for arg in &args.cmake_args {
-        cmd.arg(format!("-D{arg}"));
+    let mut definitions = deps::cmake_definitions(path, args, config)?;
+    definitions.extend(args.cmake_args.iter().cloned());
+    definitions.extend(get_cmake_definitions(config));
+
+    if args.coverage {
+        coverage::add_definitions(&mut definitions, config);
     }
 
-    for arg in get_cmake_definitions(config) {
+    workspace::add_prefix_paths(&mut definitions, &args.prefix_paths);
+
+    for arg in definitions {
         cmd.arg(format!("-D{arg}"));
     }
 
-    let cmd_str = format_cmd(&cmd);
-    debug!("CMD: {cmd_str}");
-    let mut process = cmd.spawn().map_err(|e| format!("Spawning command `{cmd_str}` failed with `{e}`"))?;
-    process.wait().map_err(|e| format!("Command `{cmd_str}` did not start; {e}"))
+    for name in &args.cmake_unset {
+        cmd.args(["-U", name]);
+    }
+
+    Ok(cmd)
 }
 
-/// Invoke CMake's build command.
+/// Invoke CMake's configure command.
+///
+/// Inline toolchains are written into the build directory beforehand.
 ///
 /// # Errors
 ///
-/// Returns an error, if the process cannot be started.
-pub fn build(path: &Path, args: &Args) -> Result<ExitStatus, String> {
+/// Returns an error, if the toolchain file cannot be written or the process cannot be started.
+pub fn configure(path: &Path, args: &Args, config: &Config) -> Result<ExitStatus, String> {
+    if let Some(toolchain) = get_toolchain(config) {
+        if let Some(content) = toolchain.content() {
+            let file = toolchain.path(args.project(), path);
+
+            if args.dry_run {
+                println!("mkdir -p {}", path.display());
+                print!("cat > {} <<'EOF'\n{content}EOF\n", file.display());
+            } else {
+                fs::create_dir_all(path).map_err(|e| format!("Failed to create build directory: {e}"))?;
+                fs::write(&file, content).map_err(|e| format!("Failed to write toolchain file: {e}"))?;
+                debug!("Toolchain file is written to `{}`.", file.display());
+            }
+        }
+    }
+
+    execute_logged(&mut configure_cmd(path, args, config)?, args.dry_run, logs::Log::new(path, "configure", config))
+}
+
+/// Parse a load average limit, a finite non-negative number.
+///
+/// ```
+/// use baldr::parse_load_average;
+///
+/// assert_eq!(parse_load_average("7.5"), Ok(7.5));
+/// assert!(parse_load_average("-1").is_err());
+/// assert!(parse_load_average("NaN").is_err());
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the value is not a number, or it is negative or not finite.
+pub fn parse_load_average(value: &str) -> Result<f64, String> {
+    value.trim().parse::<f64>()
+        .ok()
+        .filter(|x| x.is_finite() && *x >= 0.0)
+        .ok_or_else(|| format!("Invalid load average `{value}`, expected a non-negative number"))
+}
+
+/// Generator of the build directory, from its cache or else from the environment like CMake does.
+///
+/// Empty if it is CMake's default.
+fn generator(path: &Path) -> String {
+    cmake_cache::read(path).ok()
+        .and_then(|x| cmake_cache::get(&x, "CMAKE_GENERATOR").map(String::from))
+        .or_else(|| env::var("CMAKE_GENERATOR").ok())
+        .unwrap_or_default()
+}
+
+/// Assemble CMake's build command.
+///
+/// The load average limit is only given to the build tools supporting it, i.e. Make (the default
+/// generator) and Ninja.
+pub fn build_cmd(path: &Path, args: &Args) -> Command {
     let mut cmd = Command::new("cmake");
     cmd.args([
         "--build", &path.to_string_lossy(),
         "--target", &args.target,
         "--",
-        "-j", args.jobs.to_string().as_str()
+        "-j", args.jobs().to_string().as_str()
     ]);
 
-    let cmd_str = format_cmd(&cmd);
+    if let Some(load) = args.load_average {
+        let generator = generator(path);
+
+        if generator.is_empty() || generator.contains("Makefiles") || generator.starts_with("Ninja") {
+            cmd.args(["-l", &load.to_string()]);
+        } else {
+            warn!("Load average limit is not supported by the `{generator}` generator, ignoring it.");
+        }
+    }
+
+    cmd
+}
+
+/// Invoke CMake's build command.
+///
+/// # Errors
+///
+/// Returns an error, if the process cannot be started.
+pub fn build(path: &Path, args: &Args, config: &Config) -> Result<ExitStatus, String> {
+    execute_logged(&mut build_cmd(path, args), args.dry_run, logs::Log::new(path, "build", config))
+}
+
+/// Run a command in its own process group and wait for it to finish.
+///
+/// In dry-run mode the command is only printed to `stdout` and reported as successful.
+///
+/// # Errors
+///
+/// Returns an error, if the process cannot be started, or baldr is interrupted meanwhile (see
+/// [`process::wait`]).
+pub fn execute(cmd: &mut Command, dry_run: bool) -> Result<ExitStatus, String> {
+    execute_logged(cmd, dry_run, None)
+}
+
+/// Run a command like [`execute`], but in baldr's process group, so it can prompt on the
+/// terminal (e.g. a hook running `sudo`), see [`process::spawn`].
+///
+/// # Errors
+///
+/// Returns an error, if the process cannot be started, or baldr is interrupted meanwhile.
+pub fn execute_interactive(cmd: &mut Command, dry_run: bool) -> Result<ExitStatus, String> {
+    spawn_and_wait(cmd, dry_run, None, true)
+}
+
+/// Run a command like [`execute`], capturing its output into the log if one is given, see
+/// [`logs`].
+///
+/// The command is run without capturing if the log file cannot be created.
+///
+/// # Errors
+///
+/// Returns an error, if the process cannot be started, or baldr is interrupted meanwhile.
+pub fn execute_logged(cmd: &mut Command, dry_run: bool, log: Option<logs::Log>) -> Result<ExitStatus, String> {
+    spawn_and_wait(cmd, dry_run, log, false)
+}
+
+fn spawn_and_wait(cmd: &mut Command, dry_run: bool, log: Option<logs::Log>, interactive: bool) -> Result<ExitStatus, String> {
+    let cmd_str = format_cmd(cmd);
+
+    if dry_run {
+        println!("{cmd_str}");
+        return Ok(ExitStatus::from_raw(0));
+    }
+
+    let mut capture = log.and_then(|x| x.start(cmd).map_err(|e| warn!("{e}, not logging")).ok());
+
     debug!("CMD: {cmd_str}");
-    let mut process = cmd.spawn().map_err(|e| format!("Spawning command `{cmd_str}` failed with `{e}`"))?;
-    process.wait().map_err(|e| format!("Command `{cmd_str}` did not start; {e}"))
+    let mut child = process::spawn(cmd, interactive).map_err(|e| format!("Spawning command `{cmd_str}` failed with `{e}`"))?;
+
+    if let Some(capture) = &mut capture {
+        capture.tee(&mut child);
+    }
+
+    let outcome = process::wait(&mut child, None).map_err(|e| format!("Command `{cmd_str}` did not start; {e}"))?;
+
+    if let Some(capture) = capture {
+        capture.finish(&outcome);
+    }
+
+    if let Some(signal) = outcome.interrupted {
+        return Err(format!("Command `{cmd_str}` has been interrupted by {}", process::signal_name(signal)));
+    }
+
+    Ok(outcome.status)
+}
+
+/// Find an executable in the directories of `PATH`.
+pub fn find_in_path(binary: &str) -> Option<PathBuf> {
+    env::var_os("PATH").and_then(|paths| {
+        env::split_paths(&paths)
+            .map(|dir| dir.join(binary))
+            .find(|x| x.is_file())
+    })
 }
 
 /// Recursively searches for files in a directory and applies a callback to filter the results.
@@ -227,10 +940,32 @@ pub fn build(path: &Path, args: &Args) -> Result<ExitStatus, String> {
 pub fn find_files<F>(directory: &PathBuf, callback: F) -> Vec<String>
 where
     F: Fn(&str) -> bool,
+{
+    find_files_skipping(directory, |_| false, callback)
+}
+
+/// Recursively searches for files like [`find_files`], but does not descend into the directories
+/// for which `skip` returns true.
+///
+/// # Arguments
+/// * `directory` - The root directory where the search starts.
+/// * `skip` - A closure or function that takes a directory path and returns a boolean value indicating whether the directory should be skipped.
+/// * `callback` - A closure or function that takes a file name and returns a boolean value indicating whether the file should be included.
+///
+/// # Returns
+/// A vector of file paths that satisfy the callback condition.
+pub fn find_files_skipping<S, F>(directory: &PathBuf, skip: S, callback: F) -> Vec<String>
+where
+    S: Fn(&Path) -> bool,
+    F: Fn(&str) -> bool,
 {
     let mut found_files = Vec::new();
 
-    for entry in WalkDir::new(directory).into_iter().filter_map(|e| e.ok()) {
+    let walker = WalkDir::new(directory)
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !e.file_type().is_dir() || !skip(e.path()));
+
+    for entry in walker.filter_map(|e| e.ok()) {
         let path = entry.path();
 
         if path.is_file() {
@@ -245,9 +980,47 @@ where
     found_files
 }
 
+/// Find the built executable of the target in the build directory.
+///
+/// In dry-run mode a not yet built executable is assumed to be at the root of the build directory.
+///
+/// # Errors
+///
+/// Returns an error if the target is not specified ("all" can build multiple executables), or the
+/// executable cannot be found or it is ambiguous.
+pub fn find_executable(build_dir: &Path, target: &str, dry_run: bool) -> Result<PathBuf, String> {
+    if target == "all" {
+        return Err("Target must be specified".into());
+    }
+
+    let exes = find_files(&build_dir.to_path_buf(), |filename| filename == target);
+    let exe = match exes.len() {
+        1 => PathBuf::from(&exes[0]),
+        0 if dry_run => build_dir.join(target),
+        0 => return Err(format!("No executable found in `{}`", build_dir.display())),
+        _ => return Err(format!("Multiple executables found in `{}`", build_dir.display())),
+    };
+
+    path::absolute(exe).map_err(|e| format!("Invalid executable path: {e}"))
+}
+
+/// Name of the variable in a CMake definition, i.e. `NAME` of `NAME[:TYPE]=VALUE`.
+///
+/// ```
+/// use baldr::definition_name;
+///
+/// assert_eq!(definition_name("CMAKE_CXX_FLAGS:STRING=-Wall"), "CMAKE_CXX_FLAGS");
+/// assert_eq!(definition_name("OPTION=ON"), "OPTION");
+/// ```
+pub fn definition_name(definition: &str) -> &str {
+    let name = definition.split_once('=').map_or(definition, |(x, _)| x);
+    name.split_once(':').map_or(name, |(x, _)| x)
+}
+
 /// Format a command.
 ///
-/// Useful for debugging purposes.
+/// The working directory and environment variables set for the command are prepended and arguments
+/// are quoted when needed, so the result can be pasted into a shell as is.
 ///
 /// ```
 /// use std::process::Command;
@@ -257,24 +1030,95 @@ where
 /// cmd.args(["hello", "there"]);
 ///
 /// assert_eq!(format_cmd(&cmd), "echo hello there");
+///
+/// cmd.env("GREETING", "general kenobi");
+/// assert_eq!(format_cmd(&cmd), "GREETING='general kenobi' echo hello there");
+///
+/// cmd.env_remove("HOME");
+/// cmd.current_dir("/tmp");
+/// assert_eq!(format_cmd(&cmd), "cd /tmp && env -u HOME GREETING='general kenobi' echo hello there");
 /// ```
 pub fn format_cmd(cmd: &Command) -> String {
-    format!(
-        "{} {}",
-        cmd.get_program().to_string_lossy(),
-        cmd.get_args().map(|x| x.to_string_lossy()).collect::<Vec<_>>().join(" ")
-    )
+    let cwd = cmd.get_current_dir()
+        .map(|x| format!("cd {} &&", quote(&x.to_string_lossy())));
+
+    let unset = cmd.get_envs()
+        .filter(|(_, value)| value.is_none())
+        .map(|(key, _)| format!("-u {}", quote(&key.to_string_lossy())))
+        .collect::<Vec<_>>();
+
+    let unset = (!unset.is_empty()).then(|| format!("env {}", unset.join(" ")));
+
+    let envs = cmd.get_envs()
+        .filter_map(|(key, value)| value.map(|v| format!("{}={}", key.to_string_lossy(), quote(&v.to_string_lossy()))));
+
+    let program = std::iter::once(quote(&cmd.get_program().to_string_lossy()));
+    let args = cmd.get_args().map(|x| quote(&x.to_string_lossy()));
+
+    cwd.into_iter()
+        .chain(unset)
+        .chain(envs)
+        .chain(program)
+        .chain(args)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Quote a word for the shell if it contains anything but safe characters.
+fn quote(word: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_=+./:,@%".contains(c);
+
+    if !word.is_empty() && word.chars().all(safe) {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
 }
 
-/// Read input from `stdin`.
+/// Fingerprint of arbitrary data to detect changes (64-bit FNV-1a as hex string).
 ///
-/// # Panics
+/// Not cryptographically secure, but stable across versions unlike the standard hasher.
+///
+/// ```
+/// use baldr::fingerprint;
 ///
-/// Will panic in case of IO error. Cannot be handled in any meaningful way.
-#[allow(clippy::unwrap_used)]
-pub fn read_input() -> String {
-    io::stdout().lock().flush().unwrap();
-    io::stdin().lock().lines().next().unwrap().unwrap()
+/// assert_eq!(fingerprint(b""), "cbf29ce484222325");
+/// assert_ne!(fingerprint(b"a"), fingerprint(b"b"));
+/// ```
+pub fn fingerprint(data: &[u8]) -> String {
+    let hash = data.iter().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+
+    format!("{hash:016x}")
+}
+
+/// Format a size in bytes in a human readable way.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["K", "M", "G", "T"];
+
+    let mut size = bytes as f64;
+    let mut unit = "";
+    for x in UNITS {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = x;
+    }
+
+    if unit.is_empty() {
+        format!("{bytes}B")
+    } else {
+        format!("{size:.1}{unit}")
+    }
+}
+
+/// Read a line from `stdin`, `None` at the end of the input (e.g. `stdin` is closed) or on an
+/// error.
+pub fn read_input() -> Option<String> {
+    let _ = io::stdout().lock().flush();
+    io::stdin().lock().lines().next().and_then(Result::ok)
 }
 
 #[cfg(test)]
@@ -291,6 +1135,30 @@ mod tests {
         ).build().unwrap()
     }
 
+    #[test]
+    fn project_root() {
+        let root = env::temp_dir().join(format!("baldr-root-{}", std::process::id()));
+        let sub = root.join("lib").join("src");
+        fs::create_dir_all(&sub).unwrap();
+        fs::write(root.join("CMakeLists.txt"), "cmake_minimum_required(VERSION 3.20)\nproject (Root)\n").unwrap();
+        fs::write(root.join("lib").join("CMakeLists.txt"), "PROJECT(Lib)\n").unwrap();
+        fs::write(sub.join("CMakeLists.txt"), "add_library(lib lib.cc)\n").unwrap();
+
+        assert_eq!(find_project_root(&sub, None), Some(root.clone()));
+
+        fs::write(root.join("lib").join(".baldr.yaml"), "").unwrap();
+        assert_eq!(find_project_root(&sub, None), Some(root.join("lib")));
+        assert_eq!(find_project_root(&sub, Some(&root.join("lib"))), Some(root.clone()));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn cli() {
+        use clap::CommandFactory;
+        Args::command().debug_assert();
+    }
+
     #[test]
     fn cfg_cc() {
         assert_eq!(get_cc(&config()), "gcc");
@@ -301,6 +1169,42 @@ mod tests {
         assert_eq!(get_cxx(&config()), "g++");
     }
 
+    #[test]
+    fn toolchain_inline() {
+        let toolchain = Toolchain {
+            target: Some("aarch64-linux-gnu".into()),
+            sysroot: Some("/usr/aarch64-linux-gnu".into()),
+            ..Default::default()
+        };
+
+        assert_eq!(toolchain.name().as_deref(), Some("aarch64-linux-gnu"));
+
+        let content = toolchain.content().unwrap();
+        assert!(content.contains("set(CMAKE_SYSTEM_NAME Linux)"));
+        assert!(content.contains("set(CMAKE_SYSTEM_PROCESSOR aarch64)"));
+        assert!(content.contains("set(CMAKE_SYSROOT /usr/aarch64-linux-gnu)"));
+    }
+
+    #[test]
+    fn toolchain_file() {
+        let toolchain = Toolchain {
+            file: Some("cmake/arm-none-eabi.cmake".into()),
+            ..Default::default()
+        };
+
+        assert_eq!(toolchain.name().as_deref(), Some("arm-none-eabi"));
+        assert_eq!(toolchain.content(), None);
+        assert!(toolchain.path("project", Path::new("build")).ends_with("project/cmake/arm-none-eabi.cmake"));
+    }
+
+    #[test]
+    fn format_cmd_quoting() {
+        let mut cmd = Command::new("cmake");
+        cmd.args(["-DNAME=it's", "-DEMPTY=", ""]);
+
+        assert_eq!(format_cmd(&cmd), "cmake '-DNAME=it'\\''s' -DEMPTY= ''");
+    }
+
     #[test]
     fn cfg_cmake_definitions() {
         assert_eq!(
@@ -0,0 +1,454 @@
+//! Logs of configuring, building and running, kept in `<build-dir>/logs`.
+//!
+//! The output of each invocation is streamed to the terminal and written into its own log file
+//! with a timestamp on each line. The file starts with the command and ends with the result, e.g.
+//! `# Result: failed (exit status: 2)`. The oldest logs are removed beyond the configured count:
+//!
+//! ```yaml
+//! log:
+//!   keep: 20   # 0 disables logging
+//!   run: true  # log running the executable too, unless it is interactive
+//! ```
+//!
+//! Note that a program writing into a pipe instead of a terminal might buffer its output and
+//! disable colors. Colors are forced via `CLICOLOR_FORCE` and `CMAKE_COLOR_DIAGNOSTICS` if baldr
+//! writes to a terminal, unless `NO_COLOR` is set. Running the executable is not logged by default
+//! to keep its terminal.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use config::Config;
+use log::*;
+
+use crate::{
+    format_cmd,
+    process::{self, Outcome},
+};
+
+/// Default number of logs kept in a build directory.
+const DEFAULT_KEEP: usize = 20;
+
+/// Time the output is waited for after the process has exited, once it is idle. Processes started
+/// in the background (e.g. a compiler cache server) can keep the pipes open indefinitely.
+const DRAIN_IDLE: Duration = Duration::from_millis(500);
+
+/// Prefix of the line holding the result of the invocation.
+const RESULT_PREFIX: &str = "# Result: ";
+
+/// Directory of the logs in the build directory.
+pub fn logs_dir(build_dir: &Path) -> PathBuf {
+    build_dir.join("logs")
+}
+
+/// Local time broken down into its fields, and the milliseconds.
+fn local_time(time: SystemTime) -> (libc::tm, u32) {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = libc::time_t::try_from(since_epoch.as_secs()).unwrap_or_default();
+
+    // SAFETY: `tm` is a plain C struct, all zeros is a valid value.
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+
+    // SAFETY: the pointers are valid for the call, `localtime_r` is thread-safe.
+    unsafe { libc::localtime_r(&secs, &mut tm) };
+
+    (tm, since_epoch.subsec_millis())
+}
+
+/// Format the time of a line, e.g. `14:03:27.512`.
+fn format_time(time: SystemTime) -> String {
+    let (tm, millis) = local_time(time);
+    format!("{:02}:{:02}:{:02}.{millis:03}", tm.tm_hour, tm.tm_min, tm.tm_sec)
+}
+
+/// Name of the log file, e.g. `20240611-140327.512-build.log`, sorting chronologically.
+fn file_name(time: SystemTime, phase: &str) -> String {
+    let (tm, millis) = local_time(time);
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}.{millis:03}-{phase}.log",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec,
+    )
+}
+
+/// Number of logs kept, 0 if logging is disabled.
+pub fn get_keep(cfg: &Config) -> usize {
+    cfg.get_int("log.keep").ok()
+        .and_then(|x| usize::try_from(x).ok())
+        .unwrap_or(DEFAULT_KEEP)
+}
+
+/// Whether running the executable is logged.
+pub fn logs_runs(cfg: &Config) -> bool {
+    cfg.get_bool("log.run").unwrap_or(false)
+}
+
+/// Log files in the build directory, the oldest first.
+pub fn list(build_dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(logs_dir(build_dir)) else {
+        return Vec::new();
+    };
+
+    let mut files = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|x| x.extension().is_some_and(|e| e == "log"))
+        .collect::<Vec<_>>();
+
+    files.sort();
+    files
+}
+
+/// Result recorded at the end of the log, `None` if the invocation has not finished.
+pub fn result(content: &str) -> Option<&str> {
+    content.lines().last().and_then(|x| x.strip_prefix(RESULT_PREFIX))
+}
+
+/// Describe how the process has finished, see [`result`].
+///
+/// ```
+/// use std::os::unix::process::ExitStatusExt;
+/// use std::process::ExitStatus;
+/// use std::time::Duration;
+/// use baldr::{logs::describe, process::Outcome};
+///
+/// let outcome = Outcome {
+///     status: ExitStatus::from_raw(2 << 8),
+///     timed_out: false,
+///     interrupted: None,
+///     elapsed: Duration::ZERO,
+///     max_rss: 0,
+///     cpu_time: Duration::ZERO,
+/// };
+///
+/// assert_eq!(describe(&outcome), "failed (exit status: 2)");
+/// ```
+pub fn describe(outcome: &Outcome) -> String {
+    if let Some(signal) = outcome.interrupted {
+        format!("interrupted by {}", process::signal_name(signal))
+    } else if outcome.timed_out {
+        "timed out".into()
+    } else if outcome.status.success() {
+        "success".into()
+    } else {
+        format!("failed ({})", outcome.status)
+    }
+}
+
+/// Remove the oldest logs, so that at most `keep` remain.
+fn rotate(build_dir: &Path, keep: usize) {
+    let files = list(build_dir);
+
+    for file in files.iter().take(files.len().saturating_sub(keep)) {
+        if let Err(e) = fs::remove_file(file) {
+            debug!("Failed to remove log `{}`: {e}", file.display());
+        }
+    }
+}
+
+/// Log file of an invocation to be captured, see [`Log::start`].
+pub struct Log {
+    build_dir: PathBuf,
+    phase: String,
+    keep: usize,
+}
+
+impl Log {
+    /// Log of the phase (e.g. `build`) in the build directory, `None` if logging is disabled.
+    pub fn new(build_dir: &Path, phase: &str, cfg: &Config) -> Option<Self> {
+        let keep = get_keep(cfg);
+
+        (keep > 0).then(|| Self {
+            build_dir: build_dir.to_path_buf(),
+            phase: phase.to_string(),
+            keep,
+        })
+    }
+
+    /// Create the log file and pipe the output of the command, the older logs are rotated.
+    ///
+    /// The output is only streamed after the process is spawned, see [`Capture::tee`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the log file cannot be created.
+    pub fn start(self, cmd: &mut Command) -> Result<Capture, String> {
+        let dir = logs_dir(&self.build_dir);
+        fs::create_dir_all(&dir).map_err(|e| format!("Failed to create `{}`: {e}", dir.display()))?;
+
+        let now = SystemTime::now();
+        let path = dir.join(file_name(now, &self.phase));
+        let mut file = File::create(&path).map_err(|e| format!("Failed to create log `{}`: {e}", path.display()))?;
+
+        writeln!(file, "# {}\n# Started: {}", format_cmd(cmd), format_time(now))
+            .map_err(|e| format!("Failed to write log `{}`: {e}", path.display()))?;
+
+        rotate(&self.build_dir, self.keep);
+
+        if io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none() {
+            cmd.env("CLICOLOR_FORCE", "1");
+            cmd.env("CMAKE_COLOR_DIAGNOSTICS", "ON");
+        }
+
+        cmd.stdout(Stdio::piped());
+        cmd.stderr(Stdio::piped());
+
+        let sink = Sink {
+            file: Mutex::new(file),
+            last_output: Mutex::new(Instant::now()),
+            closed: AtomicBool::new(false),
+        };
+
+        Ok(Capture { path, sink: Arc::new(sink), threads: Vec::new() })
+    }
+}
+
+/// Output of a running process being written into its log, see [`Log::start`].
+pub struct Capture {
+    path: PathBuf,
+    sink: Arc<Sink>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+/// Log file the output is streamed into.
+struct Sink {
+    file: Mutex<File>,
+
+    /// When the output was last read
+    last_output: Mutex<Instant>,
+
+    /// Whether the result is recorded, the output is not streamed anymore then
+    closed: AtomicBool,
+}
+
+/// Copy the output to the terminal as it comes, and into the log with a timestamp on each line.
+fn copy(mut input: impl Read, mut output: impl Write, sink: &Sink) {
+    let mut buffer = [0; 8192];
+    let mut line_start = true;
+
+    loop {
+        let n = match input.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                debug!("Failed to read the output: {e}");
+                break;
+            },
+        };
+
+        if sink.closed.load(Ordering::SeqCst) {
+            return;
+        }
+        if let Ok(mut last_output) = sink.last_output.lock() {
+            *last_output = Instant::now();
+        }
+
+        let _ = output.write_all(&buffer[..n]).and_then(|()| output.flush());
+
+        let time = format_time(SystemTime::now());
+        let mut log = Vec::with_capacity(n);
+        for &byte in &buffer[..n] {
+            if line_start {
+                log.extend_from_slice(format!("[{time}] ").as_bytes());
+            }
+            log.push(byte);
+            line_start = byte == b'\n';
+        }
+
+        if let Ok(mut file) = sink.file.lock() {
+            if !sink.closed.load(Ordering::SeqCst) {
+                let _ = file.write_all(&log);
+            }
+        }
+    }
+
+    if !line_start {
+        if let Ok(mut file) = sink.file.lock() {
+            if !sink.closed.load(Ordering::SeqCst) {
+                let _ = file.write_all(b"\n");
+            }
+        }
+    }
+}
+
+impl Capture {
+    /// Stream the output of the spawned process to the terminal and into the log.
+    pub fn tee(&mut self, child: &mut Child) {
+        if let Some(stdout) = child.stdout.take() {
+            let sink = Arc::clone(&self.sink);
+            self.threads.push(thread::spawn(move || copy(stdout, io::stdout(), &sink)));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            let sink = Arc::clone(&self.sink);
+            self.threads.push(thread::spawn(move || copy(stderr, io::stderr(), &sink)));
+        }
+    }
+
+    /// Wait for the output to be streamed, and record the result of the process.
+    ///
+    /// The output is not waited for once it is idle for [`DRAIN_IDLE`] after the process has
+    /// exited, as its descendants might keep the pipes open.
+    pub fn finish(self, outcome: &Outcome) {
+        if let Ok(mut last_output) = self.sink.last_output.lock() {
+            *last_output = Instant::now();
+        }
+
+        while !self.threads.iter().all(JoinHandle::is_finished) {
+            let idle = self.sink.last_output.lock().map_or(Duration::ZERO, |x| x.elapsed());
+            if idle >= DRAIN_IDLE {
+                debug!("Output is still open after the process has exited, not waiting for it");
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        if let Ok(mut file) = self.sink.file.lock() {
+            self.sink.closed.store(true, Ordering::SeqCst);
+
+            if let Err(e) = writeln!(file, "{RESULT_PREFIX}{}", describe(outcome)) {
+                warn!("Failed to write log `{}`: {e}", self.path.display());
+            }
+        }
+    }
+}
+
+/// Print the content, through `$PAGER` (or `less`) if `stdout` is a terminal.
+fn page(content: &str) {
+    if io::stdout().is_terminal() {
+        let pager = env::var("PAGER").unwrap_or_else(|_| "less -R".into());
+        let pager = pager.split_whitespace().collect::<Vec<_>>();
+
+        if let Some((program, pager_args)) = pager.split_first() {
+            let spawned = Command::new(program)
+                .args(pager_args)
+                .stdin(Stdio::piped())
+                .spawn();
+
+            if let Ok(mut child) = spawned {
+                if let Some(mut stdin) = child.stdin.take() {
+                    let _ = stdin.write_all(content.as_bytes());
+                }
+                let _ = child.wait();
+                return;
+            }
+        }
+    }
+
+    print!("{content}");
+}
+
+/// List the logs of the build directory, or show the most recent (failed) one.
+///
+/// # Errors
+///
+/// Returns an error if there is no such log or it cannot be read.
+pub fn show(build_dir: &Path, last: bool, failed: bool) -> Result<(), String> {
+    let files = list(build_dir);
+
+    if files.is_empty() {
+        return Err(format!("No logs in `{}`", logs_dir(build_dir).display()));
+    }
+
+    if !last && !failed {
+        for file in &files {
+            let content = fs::read_to_string(file).unwrap_or_default();
+            println!(
+                "{}  {}",
+                file.file_name().unwrap_or_default().to_string_lossy(),
+                result(&content).unwrap_or("unfinished")
+            );
+        }
+        return Ok(());
+    }
+
+    let read = |file: &PathBuf| fs::read_to_string(file)
+        .map_err(|e| format!("Failed to read log `{}`: {e}", file.display()));
+
+    for file in files.iter().rev() {
+        let content = read(file)?;
+
+        if !failed || result(&content).is_some_and(|x| x != "success") {
+            println!("# {}", file.display());
+            page(&content);
+            return Ok(());
+        }
+    }
+
+    Err(format!("No failed invocation in the logs of `{}`", build_dir.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotation() {
+        let dir = env::temp_dir().join(format!("baldr-logs-{}", std::process::id()));
+        fs::create_dir_all(logs_dir(&dir)).unwrap();
+
+        for name in ["20240101-000000.000-build.log", "20240101-000001.000-run.log", "20240101-000002.000-build.log", "notes.txt"] {
+            fs::write(logs_dir(&dir).join(name), "").unwrap();
+        }
+
+        rotate(&dir, 2);
+
+        let names = list(&dir).iter()
+            .map(|x| x.file_name().unwrap().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["20240101-000001.000-run.log", "20240101-000002.000-build.log"]);
+        assert!(logs_dir(&dir).join("notes.txt").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn inherited_pipes() {
+        let dir = env::temp_dir().join(format!("baldr-logs-pipes-{}", std::process::id()));
+        let cfg = Config::builder().build().unwrap();
+
+        // The background `sleep` keeps the pipes open after the shell has exited.
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo output; sleep 30 &"]);
+
+        let mut capture = Log::new(&dir, "run", &cfg).unwrap().start(&mut cmd).unwrap();
+        let mut child = process::spawn(&mut cmd, false).unwrap();
+        capture.tee(&mut child);
+
+        let start = Instant::now();
+        let outcome = process::wait(&mut child, None).unwrap();
+        capture.finish(&outcome);
+        assert!(start.elapsed() < Duration::from_secs(10));
+
+        let content = fs::read_to_string(&list(&dir)[0]).unwrap();
+        assert!(content.contains("] output\n"));
+        assert_eq!(result(&content), Some("success"));
+
+        // SAFETY: plain syscall, the process group lives on in the background `sleep`.
+        unsafe { libc::kill(-libc::pid_t::try_from(child.id()).unwrap(), libc::SIGKILL) };
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn run_logging() {
+        assert!(!logs_runs(&Config::default()));
+        assert!(logs_runs(&Config::builder().set_override("log.run", true).unwrap().build().unwrap()));
+    }
+
+    #[test]
+    fn results() {
+        assert_eq!(result("# cmake\n[10:00:00.000] x\n# Result: success\n"), Some("success"));
+        assert_eq!(result("# cmake\n[10:00:00.000] x\n"), None);
+    }
+}
@@ -3,18 +3,46 @@ use config::Config;
 
 use baldr::{
     Args,
+    CacheCommands,
+    Commands,
+    ConfigCommands,
+    WorkspaceCommands,
+    bench,
     build,
+    cmake_cache,
+    config_layers,
     configure,
-    find_files,
+    configure_cmd,
+    coverage,
+    deps,
+    dirs,
+    execute,
+    find_executable,
     format_cmd,
+    format_size,
+    formatting,
+    get_toolchain,
+    hooks,
+    init,
+    install,
+    launch,
+    logs,
+    process,
     read_config,
+    reconfigure,
     read_input,
+    settings,
+    tidy,
+    workspace,
 };
 
 use log::*;
 
 use std::{
-    io,
+    io::{
+        self,
+        IsTerminal,
+    },
     fs,
     fmt,
     os::unix::{
@@ -27,16 +55,19 @@ use std::{
         PathBuf
     },
     process::{
-        self,
         Command,
-    }
+        Stdio,
+    },
+    time::Duration,
 };
 
 struct BuildPath<'a> {
     project: &'a str,
     build_type: &'a str,
     compiler_path: &'a str,
+    toolchain: Option<&'a str>,
     sanitizer: Option<&'a str>,
+    coverage: bool,
     custom_dir: Option<&'a str>
 }
 
@@ -47,7 +78,9 @@ impl<'a> BuildPath<'a> {
     /// - build type in lowercase, e.g. `debug` or `release`
     /// - compiler name - source: CC and CXX
     /// - compiler version (if not the default is in use) - source: CC and CXX
+    /// - toolchain (if cross-compiling)
     /// - sanitizers (if used)
+    /// - coverage (if instrumented)
     ///
     fn to_path(&self) -> PathBuf {
         let compiler = match self.compiler_path {
@@ -59,13 +92,18 @@ impl<'a> BuildPath<'a> {
             Some(dir) => dir.into(),
             None => {
                 format!(
-                    "{}{}{}",
+                    "{}{}{}{}{}",
                     self.build_type.to_lowercase(),
                     compiler,
+                    match self.toolchain {
+                        Some(toolchain) => format!("-{toolchain}"),
+                        None => String::new()
+                    },
                     match self.sanitizer {
                         Some(san) => format!("-{san}"),
                         None => String::new()
-                    }
+                    },
+                    if self.coverage { "-coverage" } else { "" }
                 )
             }
         };
@@ -82,15 +120,41 @@ impl fmt::Display for BuildPath<'_> {
     }
 }
 
-fn create_compile_cmd_symlink(src: &Path, dst: &Path) -> Result<(), io::Error> {
+/// Create a symlink for `compile_commands.json` in `dst` pointing into the build directory `src`.
+///
+/// An existing symlink pointing to another build directory is repointed if `follow` is set, so the
+/// language server always sees the most recent build. Regular files are left untouched.
+fn create_compile_cmd_symlink(src: &Path, dst: &Path, follow: bool, dry_run: bool) -> Result<(), io::Error> {
     let file = "compile_commands.json";
     let src = path::absolute(src.join(file))?;
     let dst = dst.join(file);
 
+    if dry_run {
+        println!("ln -sfn {} {}", src.display(), dst.display());
+        return Ok(());
+    }
+
+    // E.g. generators not supporting `CMAKE_EXPORT_COMPILE_COMMANDS`.
+    if !src.exists() {
+        warn!("Build directory has no `compile_commands.json`, not linking it.");
+        return Ok(());
+    }
+
     match fs::exists(&dst) {
         Ok(true) => {
-            debug!("`compile_commands.json` symlink already exists and is valid.");
-            Ok(())
+            if !fs::symlink_metadata(&dst)?.is_symlink() {
+                warn!("`compile_commands.json` is not a symlink, leaving it untouched.");
+                return Ok(());
+            }
+
+            if !follow || fs::canonicalize(&dst)? == fs::canonicalize(&src)? {
+                debug!("`compile_commands.json` symlink already exists and is valid.");
+                return Ok(());
+            }
+
+            fs::remove_file(&dst)?;
+            info!("Repointing `compile_commands.json` symlink to `{}`...", src.display());
+            symlink(src, &dst)
         }
         Ok(false) => {
             match fs::remove_file(&dst) {
@@ -105,11 +169,84 @@ fn create_compile_cmd_symlink(src: &Path, dst: &Path) -> Result<(), io::Error> {
     }
 }
 
-fn delete_build_dir(build_dir: &Path, confirm: bool) -> Result<bool, String> {
+/// Find a build directory by path, name under `<project>/build` or a unique prefix of the name.
+fn find_build_dir(project: &str, dir: &str) -> Result<PathBuf, String> {
+    let named = Path::new(project).join("build").join(dir);
+
+    if Path::new(dir).join("compile_commands.json").exists() {
+        return Ok(PathBuf::from(dir));
+    }
+    if named.exists() {
+        return Ok(named);
+    }
+
+    let candidates = dirs::list(project)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|x| x.name.starts_with(dir))
+        .collect::<Vec<_>>();
+
+    match candidates.as_slice() {
+        [x] => Ok(x.path.clone()),
+        [] => Ok(named),
+        _ => {
+            let names = candidates.iter().map(|x| format!("`{}`", x.name)).collect::<Vec<_>>();
+            Err(format!("Build directory `{dir}` is ambiguous: {}", names.join(", ")))
+        },
+    }
+}
+
+/// Point `compile_commands.json` of the project to the given build directory.
+///
+/// The build directory is either a path, the name of a directory under `<project>/build` or a
+/// unique prefix of it (e.g. `release-clang`). If it is not given, it is the one of the build
+/// options (e.g. `--build-type Release`).
+///
+/// # Errors
+///
+/// Returns an error if the build directory is ambiguous, has no compilation database or the
+/// symlink cannot be created.
+fn use_build_dir(args: &Args, dir: Option<&str>, default: &Path) -> Result<(), String> {
+    let build_dir = match dir {
+        Some(dir) => find_build_dir(args.project(), dir)?,
+        None => default.to_path_buf(),
+    };
+
+    if !build_dir.join("compile_commands.json").exists() {
+        return Err(format!("No `compile_commands.json` in `{}`, is it configured?", build_dir.display()));
+    }
+
+    create_compile_cmd_symlink(&build_dir, Path::new(args.project()), true, args.dry_run)
+        .map_err(|e| format!("Failed to create a symlink for `compile_commands.json`: {e}"))?;
+
+    if !args.dry_run {
+        let cache = cmake_cache::read(&build_dir).unwrap_or_default();
+        let details = ["CMAKE_BUILD_TYPE", "CMAKE_CXX_COMPILER"].iter()
+            .filter_map(|x| cmake_cache::get(&cache, x))
+            .filter(|x| !x.is_empty())
+            .map(|x| Path::new(x).file_name().map_or_else(|| x.to_string(), |f| f.to_string_lossy().into_owned()))
+            .collect::<Vec<_>>();
+
+        println!(
+            "clangd now uses `{}`{}; restart the language server if it does not pick up the change.",
+            build_dir.display(),
+            if details.is_empty() { String::new() } else { format!(" ({})", details.join(", ")) }
+        );
+    }
+
+    Ok(())
+}
+
+fn delete_build_dir(build_dir: &Path, confirm: bool, dry_run: bool) -> Result<bool, String> {
+    if dry_run {
+        println!("rm -rf {}", build_dir.display());
+        return Ok(true);
+    }
+
     if confirm {
         eprint!("Are you sure to remove `{}` (press 'y' to proceed): ", build_dir.to_string_lossy());
 
-        if read_input() != "y" {
+        if read_input().as_deref() != Some("y") {
             info!("Skipping clean build.");
             return Ok(false);
         }
@@ -124,84 +261,332 @@ fn delete_build_dir(build_dir: &Path, confirm: bool) -> Result<bool, String> {
     Ok(true)
 }
 
-/// Run the built executable with the given arguments optionall under a debugger.
+/// Assemble the command running the built executable with the given arguments optionally under
+/// a debugger.
 ///
 /// Supported debuggers:
 /// - `gdb`: `gdb --args <EXECUTABLE> <ARGS> ...`
 /// - `lldb`: lldb <EXECUTABLE> <ARGS> ...`
 ///
+/// The environment and working directory are set according to the section of the profile, `run`
+/// or a named run configuration, see [`launch`]. A debugger of the profile runs the executable
+/// under it even without `--debug`, and a wrapper of the profile prefixes the whole command.
+///
+/// Cross-built executables are launched through the emulator of the toolchain if one is configured,
+/// e.g. `qemu-aarch64 -L <SYSROOT> <EXECUTABLE> <ARGS> ...`.
+///
 /// # Errors
 ///
 /// Returns an error in the following cases:
-/// - Target is not specified ("all" can build multiple executables)
+/// - The executable cannot be found, see [`find_executable`]
 /// - Debugger is not configured or is unsupported when it is asked to run under debugger
-/// - The built executable returns with other than exit code 0
-fn run(target: &String, build_dir: &PathBuf, config: &Config, args: &Args) -> Result<(), String> {
-    if target == "all" {
-        return Err("Target must be specified".into());
-    }
-
-    let exes = find_files(build_dir, |filename| { filename == *target });
-    match exes.len() {
-        1 => {
-            let mut cmd = (|| -> Result<Command, String> {
-                if args.debug {
-                    let debugger = config.get::<String>("debugger")
-                        .map_err(|e| format!("No debugger is configured: {e}"))?;
-
-                    let mut cmd = Command::new(&debugger);
-                    if debugger == "gdb" {
-                        cmd.arg("--args");
-                        cmd.arg(&exes[0]);
-                    } else if debugger == "lldb" {
-                        cmd.arg(&exes[0]);
-                    } else {
-                        return Err("Unsupported debugger: `{debugger}`!".into());
+/// - Debugger is asked for an executable run through an emulator
+fn run_cmd(target: &str, build_dir: &Path, config: &Config, args: &Args, profile: &launch::Profile) -> Result<Command, String> {
+    let exe = find_executable(build_dir, target, args.dry_run)?;
+
+    let emulator = get_toolchain(config).map(|x| x.emulator).unwrap_or_default();
+
+    let cmd = if args.debug || profile.debugger.is_some() {
+        if !emulator.is_empty() {
+            return Err("Running under debugger is not supported with an emulator".into());
+        }
+
+        let debugger = match &profile.debugger {
+            Some(x) => x.clone(),
+            None => config.get::<String>("debugger").map_err(|e| format!("No debugger is configured: {e}"))?,
+        };
+
+        let mut cmd = Command::new(&debugger);
+        if debugger == "gdb" {
+            cmd.arg("--args");
+            cmd.arg(&exe);
+        } else if debugger == "lldb" {
+            cmd.arg(&exe);
+        } else {
+            return Err(format!("Unsupported debugger: `{debugger}`!"));
+        }
+        cmd
+    } else if let Some((program, emulator_args)) = emulator.split_first() {
+        let mut cmd = Command::new(program);
+        cmd.args(emulator_args);
+        cmd.arg(&exe);
+        cmd
+    } else {
+        Command::new(&exe)
+    };
+
+    let mut cmd = match profile.wrapper.split_first() {
+        Some((program, wrapper_args)) => {
+            let mut wrapped = Command::new(program);
+            wrapped.args(wrapper_args);
+            wrapped.arg(cmd.get_program());
+            wrapped.args(cmd.get_args());
+            wrapped
+        },
+        None => cmd,
+    };
+
+    cmd.args(&args.exe_args);
+    launch::apply(&mut cmd, args.project(), config, &profile.section, &args.env)?;
+    Ok(cmd)
+}
+
+/// Executable prepared to be run, see [`prepare_run`].
+struct Runnable {
+    cmd: Command,
+    timeout: Option<Duration>,
+    limits: process::Limits,
+
+    /// Whether the executable is run under a debugger or reads the terminal, see [`process::spawn`]
+    interactive: bool,
+
+    /// Log capturing the output, see [`logs`]
+    log: Option<logs::Log>,
+}
+
+/// Assemble the command of the built executable with its timeout and resource limits.
+///
+/// The executable is stopped if it exceeds the timeout given either by `--timeout` or by the
+/// `timeout` of the profile's section (e.g. `runs.server.timeout`, falling back to `run.timeout`),
+/// and resource limits are applied likewise, see [`process`].
+///
+/// # Errors
+///
+/// Returns an error if the command cannot be assembled (see [`run_cmd`]), or the timeout or
+/// resource limits are malformed.
+fn prepare_run(target: &str, build_dir: &Path, config: &Config, args: &Args, profile: &launch::Profile) -> Result<Runnable, String> {
+    let mut cmd = run_cmd(target, build_dir, config, args, profile)?;
+
+    let timeout = match args.timeout.clone().or_else(|| launch::get_string(config, &profile.section, "timeout")) {
+        Some(x) => Some(process::parse_duration(&x)?),
+        None => None,
+    };
+
+    let mut limits = process::Limits::default();
+    for section in launch::sections(&profile.section) {
+        limits = process::get_limits(config, section)?.or(limits);
+    }
+    process::set_limits(&mut cmd, &limits);
+
+    if args.dry_run {
+        if let Some(x) = timeout {
+            println!("# Timeout: {}s", x.as_secs());
+        }
+        if !limits.is_empty() {
+            println!("# Limits: {limits}");
+        }
+    }
+
+    let debugger = args.debug || profile.debugger.is_some();
+    let interactive = debugger || io::stdin().is_terminal();
+
+    // Interactive runs (e.g. debugger sessions) are not logged, as they need the terminal.
+    let log = if interactive || !logs::logs_runs(config) { None } else { logs::Log::new(build_dir, "run", config) };
+
+    Ok(Runnable { cmd, timeout, limits, interactive, log })
+}
+
+impl Runnable {
+    /// Run the executable once and wait for it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the executable returns with other than exit code 0, exceeds a limit, or
+    /// baldr is interrupted meanwhile.
+    fn spawn(&mut self) -> Result<process::Outcome, String> {
+        let cmd_str = format_cmd(&self.cmd);
+        debug!("CMD: {cmd_str}");
+        let mut capture = self.log.take().and_then(|x| x.start(&mut self.cmd).map_err(|e| warn!("{e}, not logging")).ok());
+
+        let mut child = process::spawn(&mut self.cmd, self.interactive).map_err(|e| format!("Failed to run the built executable: {e}"))?;
+
+        if let Some(capture) = &mut capture {
+            capture.tee(&mut child);
+        }
+
+        let outcome = process::wait(&mut child, self.timeout).map_err(|e| format!("Command `{cmd_str}` did not start: {e}"))?;
+        let result = outcome.status;
+
+        if let Some(capture) = capture {
+            capture.finish(&outcome);
+        }
+
+        if let Some(signal) = outcome.interrupted {
+            return Err(format!("Process has been interrupted by {}", process::signal_name(signal)));
+        }
+
+        if let Some(timeout) = self.timeout.filter(|_| outcome.timed_out) {
+            return Err(format!("Process has exceeded the timeout of {}s", timeout.as_secs()));
+        }
+
+        // The kernel sends `SIGKILL` after `SIGXCPU` if the soft limit is ignored, other kills (e.g.
+        // by the OOM killer) are told apart by the CPU time spent.
+        let cpu_time = outcome.cpu_time.as_secs();
+        let cpu_exceeded = |signal| self.limits.cpu.is_some_and(|x| signal == libc::SIGXCPU || (signal == libc::SIGKILL && cpu_time >= x));
+
+        match result.code() {
+            Some(0) => Ok(outcome),
+            Some(code) => Err(format!("Process has returned with exit code: {code}")),
+            None => match result.signal() {
+                Some(code) if cpu_exceeded(code) => Err(format!(
+                    "Process has exceeded the CPU time limit of {}s",
+                    self.limits.cpu.unwrap_or_default()
+                )),
+                Some(code) => {
+                    let mut message = format!("Process has been made to exit with signal: {} ({code})", process::signal_name(code));
+
+                    if let Some(memory) = self.limits.memory.filter(|_| [libc::SIGABRT, libc::SIGSEGV, libc::SIGBUS].contains(&code)) {
+                        message += &format!(", the memory limit of {} might have been exceeded", format_size(memory));
                     }
-                    Ok(cmd)
-                } else {
-                    Ok(Command::new(&exes[0]))
-                }
-            })()?;
-
-            cmd.args(&args.exe_args);
-            let mut process = cmd.spawn().map_err(|e| format!("Failed to run the built executable: {e}"))?;
-            let cmd_str = format_cmd(&cmd);
-            let result = process.wait().map_err(|e| format!("Command `{cmd_str}` did not start: {e}"))?;
-
-            match result.code() {
-                Some(0) => Ok(()),
-                Some(code) => Err(format!("Process has returned with exit code: {code}")),
-                None => match result.signal() {
-                    Some(code) => Err(format!("Process has been made to exit with signal: {code}")),
-                    None => Err("Unknown error during checking exit code".to_string()),
-                }
+
+                    Err(message)
+                },
+                None => Err("Unknown error during checking exit code".to_string()),
             }
+        }
+    }
+}
 
-        },
-        0 => Err(format!("No executable found in `{}`", build_dir.display())),
-        _ => Err(format!("Multiple executables found in `{}`", build_dir.display())),
+/// Run the built executable.
+///
+/// # Errors
+///
+/// Returns an error in the following cases:
+/// - The command cannot be assembled, see [`prepare_run`]
+/// - The built executable returns with other than exit code 0 or exceeds a limit
+fn run(target: &str, build_dir: &Path, config: &Config, args: &Args, profile: &launch::Profile) -> Result<(), String> {
+    let mut runnable = prepare_run(target, build_dir, config, args, profile)?;
+
+    if args.coverage {
+        coverage::prepare(&mut runnable.cmd, build_dir, config, args.dry_run)?;
     }
+
+    if args.dry_run {
+        execute(&mut runnable.cmd, true)?;
+        return Ok(());
+    }
+
+    runnable.spawn().map(|_| ())
 }
 
-fn entrypoint() -> Result<(), String> {
-    let args = Args::parse();
-    let config = read_config(
-        &args.config,
-        &args.project
-    ).map_err(|e| e.to_string())?;
+/// Run the built executable repeatedly, and report the statistics of the wall time and peak RSS.
+///
+/// The output of the executable is discarded, so that printing does not distort the timing.
+///
+/// # Errors
+///
+/// Returns an error if any of the runs fails (see [`run`]), or the results cannot be saved.
+fn bench(target: &str, build_dir: &Path, config: &Config, args: &Args, profile: &launch::Profile, runs: u32) -> Result<(), String> {
+    let mut runnable = prepare_run(target, build_dir, config, args, profile)?;
+    runnable.cmd.stdout(Stdio::null());
+    runnable.log = None;
+
+    let warmup = bench::get_warmup(config);
+
+    if args.dry_run {
+        println!("# Benchmark: {runs} run(s) after {warmup} warmup run(s)");
+        execute(&mut runnable.cmd, true)?;
+        return Ok(());
+    }
+
+    info!("Benchmarking `{target}` with {runs} run(s) after {warmup} warmup run(s)...");
+
+    for i in 1..=warmup {
+        runnable.spawn().map_err(|e| format!("Warmup run {i}/{warmup}: {e}"))?;
+    }
+
+    let mut samples = Vec::new();
+    for i in 1..=runs {
+        let outcome = runnable.spawn().map_err(|e| format!("Run {i}/{runs}: {e}"))?;
+        samples.push(bench::Sample { wall: outcome.elapsed, max_rss: outcome.max_rss });
+    }
+
+    bench::report(build_dir, target, &args.exe_args, &samples, config)
+}
+
+/// List the build directories of the project, or prune the selected ones.
+///
+/// Pruning requires at least one selector, and asks for confirmation for each directory unless it
+/// is disabled.
+///
+/// # Errors
+///
+/// Returns an error if the build directories cannot be listed or deleted, or the age is malformed.
+fn manage_dirs(args: &Args, prune: bool, older_than: Option<&str>, pattern: Option<&str>) -> Result<(), String> {
+    let min_age = older_than.map(process::parse_duration).transpose()?;
+
+    let selected = dirs::list(args.project())?
+        .into_iter()
+        .filter(|x| pattern.map_or(true, |p| dirs::matches(p, &x.name)))
+        .filter(|x| min_age.map_or(true, |min| x.age().is_some_and(|age| age >= min)))
+        .collect::<Vec<_>>();
+
+    if prune {
+        if older_than.is_none() && pattern.is_none() {
+            return Err("Pruning requires `--older-than` or `--match`".into());
+        }
+
+        if selected.is_empty() {
+            info!("No build directory is selected for pruning.");
+        }
+
+        for dir in &selected {
+            delete_build_dir(&dir.path, !args.no_confirm, args.dry_run)?;
+        }
 
-    let build_dir = BuildPath{
-        project: args.project.as_str(),
+        return Ok(());
+    }
+
+    let rows = selected.iter()
+        .map(|x| [
+            format!("{} {}", if x.linked { "*" } else { " " }, x.name),
+            x.build_type.clone().unwrap_or_default(),
+            x.compiler.as_deref()
+                .and_then(|c| Path::new(c).file_name())
+                .map(|c| c.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            x.generator.clone().unwrap_or_default(),
+            format_size(x.size),
+            x.age().map(dirs::format_age).unwrap_or_default(),
+        ])
+        .collect::<Vec<_>>();
+
+    let header = ["  NAME", "TYPE", "COMPILER", "GENERATOR", "SIZE", "LAST BUILD"].map(String::from);
+    let widths = header.iter().enumerate()
+        .map(|(i, h)| rows.iter().map(|r| r[i].len()).chain([h.len()]).max().unwrap_or_default())
+        .collect::<Vec<_>>();
+
+    for row in std::iter::once(&header).chain(&rows) {
+        let line = row.iter().zip(&widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        println!("{}", line.trim_end());
+    }
+
+    Ok(())
+}
+
+/// Build directory of the project, following the naming conventions of [`BuildPath`].
+fn build_path(args: &Args, config: &Config) -> PathBuf {
+    let toolchain = get_toolchain(config).and_then(|x| x.name());
+
+    BuildPath{
+        project: args.project(),
         build_type: args.build_type.as_str(),
         compiler_path: &config.get_string("compiler.cxx").unwrap_or_default(),
+        toolchain: toolchain.as_deref(),
         sanitizer: None,
+        coverage: args.coverage || matches!(args.command, Some(Commands::Coverage)),
         custom_dir: args.build_dir.as_deref()
-    }.to_path();
-
-    info!("Using build directory: {}", build_dir.to_string_lossy());
+    }.to_path()
+}
 
-    let mut build_exists = match fs::exists(&build_dir) {
+/// Clean the build directory if requested or its cache does not match the settings, and create it.
+///
+/// Returns whether the build directory exists (i.e. it is not to be configured from scratch).
+fn prepare_build_dir(build_dir: &Path, args: &Args, config: &Config) -> Result<bool, String> {
+    let mut build_exists = match fs::exists(build_dir) {
         Ok(true) => {
             info!("Build directory already exists.");
             Ok(true)
@@ -210,34 +595,322 @@ fn entrypoint() -> Result<(), String> {
         Err(x) => Err(format!("{x}")),
     }?;
 
+    if build_exists && !args.delete {
+        let mismatches = reconfigure::cache_mismatches(build_dir, args, config);
+
+        if !mismatches.is_empty() {
+            let details = mismatches.join("; ");
+
+            if args.no_configure {
+                warn!("Build directory is configured with different settings ({details}), but configuring is skipped.");
+            } else {
+                if args.dry_run {
+                    println!("# Build directory is configured with different settings: {details}");
+                } else if args.no_confirm {
+                    warn!("Build directory is configured with different settings ({details}), cleaning it.");
+                } else {
+                    eprintln!("Build directory is configured with different settings, it needs a clean reconfigure:");
+                    for x in &mismatches {
+                        eprintln!("  {x}");
+                    }
+                }
+
+                if !delete_build_dir(build_dir, !args.no_confirm, args.dry_run)? {
+                    return Err(format!("Build directory `{}` cannot be reconfigured in place: {details}", build_dir.display()));
+                }
+                build_exists = false;
+            }
+        }
+    }
+
     if args.delete {
         if build_exists {
-            build_exists = !delete_build_dir(&build_dir, !args.no_confirm)?;
+            build_exists = !delete_build_dir(build_dir, !args.no_confirm, args.dry_run)?;
         } else {
             warn!("Build directory does not exist, there is nothing to delete!");
         }
 
         if !build_exists {
-            fs::create_dir_all(&build_dir).map_err(|e| format!("Failed to create build directory: {e}"))?;
-            info!("Build directory has been created.");
+            if args.dry_run {
+                println!("mkdir -p {}", build_dir.display());
+            } else {
+                fs::create_dir_all(build_dir).map_err(|e| format!("Failed to create build directory: {e}"))?;
+                info!("Build directory has been created.");
+            }
+        }
+    }
+
+    Ok(build_exists)
+}
+
+/// Configure the build directory if it is new, its inputs have changed or the cache is edited (with
+/// the reason and the edited definition).
+///
+/// In dry-run mode a skipped configure command is printed as a comment.
+fn configure_if_needed(
+    build_dir: &Path,
+    build_exists: bool,
+    args: &Args,
+    config: &Config,
+    cache_edit: Option<&(String, Option<String>)>,
+) -> Result<(), String> {
+    let cmd = configure_cmd(build_dir, args, config)?;
+    let inputs = reconfigure::inputs(&cmd, args, config);
+    let reason = if !build_exists {
+        Some("the build directory is new".into())
+    } else if let Some((reason, _)) = cache_edit {
+        Some(reason.clone())
+    } else {
+        reconfigure::reason(build_dir, &inputs)
+    };
+
+    match reason {
+        Some(reason) if !build_exists || !args.no_configure => {
+            info!("Configuring, as {reason}.");
+
+            if args.dry_run {
+                println!("# Configuring, as {reason}");
+            } else {
+                reconfigure::forget(build_dir);
+            }
+
+            hooks::run("pre_configure", build_dir, args, config)?;
+            deps::install(build_dir, args, config)?;
+
+            // The definitions of the dependencies (e.g. the toolchain of Conan) are only known
+            // after installing them, so the inputs are recorded as configured.
+            let inputs = reconfigure::inputs(&configure_cmd(build_dir, args, config)?, args, config);
+
+            if !configure(build_dir, args, config)?.success() {
+                return Err("Configuring failed".into());
+            }
+
+            if !args.dry_run {
+                let edited = cache_edit.and_then(|(_, x)| x.as_ref());
+                let inputs = inputs.into_iter()
+                    .filter(|(_, value)| Some(value) != edited)
+                    .collect::<Vec<_>>();
+
+                reconfigure::save(build_dir, &inputs)?;
+            }
+
+            hooks::run("post_configure", build_dir, args, config)?;
+        },
+        Some(reason) => warn!("Not configuring due to `--no-configure`, although {reason}."),
+        None => {
+            info!("Configure inputs are unchanged, skipping configuring.");
+
+            if args.dry_run {
+                println!("# Configure inputs are unchanged, skipping configuring:");
+                println!("# {}", format_cmd(&cmd));
+            }
+        },
+    }
+
+    Ok(())
+}
+
+/// Configure, build and install the members of the workspace in dependency order into the staging
+/// prefix, see [`workspace`].
+///
+/// Each member is built in its own build directory with its own configuration, the arguments apply
+/// to all of them. Members depending on others get the staging prefix prepended to
+/// `CMAKE_PREFIX_PATH`.
+fn build_workspace(args: &Args, config: &Config, build_dir: &Path) -> Result<(), String> {
+    let root = Path::new(args.project());
+
+    // Each member is built in the build directory under its own project.
+    if let Some(dir) = args.build_dir.as_deref().filter(|x| Path::new(x).is_absolute()) {
+        return Err(format!("Absolute build directory `{dir}` would be shared by the workspace members, use a relative one"));
+    }
+    let members = workspace::order(workspace::get_members(config, root)?)?;
+
+    if members.is_empty() {
+        return Err(format!("No workspace members are configured in `{}`", root.display()));
+    }
+
+    let staging = workspace::staging_dir(config, root, &build_dir.join("staging"));
+    let staging = std::path::absolute(&staging)
+        .map_err(|e| format!("Failed to resolve the staging prefix `{}`: {e}", staging.display()))?;
+
+    info!("Using staging prefix: {}", staging.display());
+
+    for member in &members {
+        if !member.path.is_dir() {
+            return Err(format!("Workspace member `{}` is not found at `{}`", member.name, member.path.display()));
+        }
+
+        let mut member_args = args.clone();
+        member_args.project = Some(member.path.to_string_lossy().into_owned());
+        member_args.command = None;
+
+        if !member.depends.is_empty() {
+            member_args.prefix_paths.push(staging.to_string_lossy().into_owned());
+        }
+
+        let member_config = read_config(&member_args.config, member_args.project()).map_err(|e| e.to_string())?;
+        let member_dir = build_path(&member_args, &member_config);
+
+        info!("Building workspace member `{}` in {}", member.name, member_dir.display());
+
+        if args.dry_run {
+            println!("# Workspace member: {}", member.name);
+            println!("# Build directory: {}", member_dir.display());
+        }
+
+        let build_exists = prepare_build_dir(&member_dir, &member_args, &member_config)?;
+        configure_if_needed(&member_dir, build_exists, &member_args, &member_config, None)?;
+
+        hooks::run("pre_build", &member_dir, &member_args, &member_config)?;
+
+        if !build(&member_dir, &member_args, &member_config)?.success() {
+            return Err(format!("Building workspace member `{}` failed", member.name));
         }
+
+        hooks::run("post_build", &member_dir, &member_args, &member_config)?;
+
+        let follow = member_config.get_bool("compile_commands.follow").unwrap_or(true);
+        create_compile_cmd_symlink(&member_dir, &member.path, follow, args.dry_run)
+            .map_err(|e| format!("Failed to create a symlink for `compile_commands.json`: {e}"))?;
+
+        install::install(&member_dir, &member_args, &staging.to_string_lossy(), None)?;
+    }
+
+    Ok(())
+}
+
+fn entrypoint() -> Result<(), String> {
+    let mut args = Args::parse();
+
+    if let Some(Commands::Init { global }) = args.command {
+        return init::run(&args, global);
+    }
+
+    args.resolve_project()?;
+
+    if let Some(Commands::Config { command }) = &args.command {
+        let layers = config_layers(&args.config, args.project()).map_err(|e| e.to_string())?;
+
+        return match command {
+            ConfigCommands::Show => settings::show(&layers),
+            ConfigCommands::Explain { key } => settings::explain(&layers, key),
+        };
+    }
+
+    let config = read_config(
+        &args.config,
+        args.project()
+    ).map_err(|e| e.to_string())?;
+
+    args.resolve_parallelism(&config)?;
+
+    let profile = match &args.command {
+        Some(Commands::Run { name, args: extra }) => {
+            let profile = launch::get_profile(&config, name)?;
+            let extra = extra.clone();
+
+            args.target = profile.target.clone().unwrap_or_else(|| args.target.clone());
+            args.exe_args = profile.args.iter().cloned().chain(extra).collect();
+            args.run = true;
+            profile
+        },
+        _ => launch::Profile::default(),
+    };
+
+    let build_dir = build_path(&args, &config);
+
+    // Edits of the cache are applied by configuring, but not recorded as configure inputs, as the
+    // cache keeps them anyway.
+    let mut cache_edit: Option<(String, Option<String>)> = None;
+
+    match &args.command {
+        Some(Commands::Cache { command: CacheCommands::Set { name, value, kind } }) => {
+            let kind = kind.clone()
+                .or_else(|| {
+                    let entries = cmake_cache::read(&build_dir).ok()?;
+                    cmake_cache::find(&entries, name).map(|x| x.kind.clone())
+                })
+                .unwrap_or_else(|| "STRING".into());
+
+            let definition = format!("{name}:{kind}={value}");
+            args.cmake_args.push(definition.clone());
+            cache_edit = Some((format!("`{name}` is set in the cache"), Some(definition)));
+        },
+        Some(Commands::Cache { command: CacheCommands::Unset { name } }) => {
+            args.cmake_unset.push(name.clone());
+            cache_edit = Some((format!("`{name}` is removed from the cache"), None));
+        },
+        _ => {},
+    }
+
+    if cache_edit.is_some() {
+        args.no_configure = false;
+    }
+
+    match &args.command {
+        Some(Commands::Dirs { prune, older_than, pattern }) => {
+            return manage_dirs(&args, *prune, older_than.as_deref(), pattern.as_deref());
+        },
+        Some(Commands::Use { dir }) => return use_build_dir(&args, dir.as_deref(), &build_dir),
+        Some(Commands::Tidy { since, fix }) => return tidy::run(&build_dir, &args, &config, since.as_deref(), *fix),
+        Some(Commands::Install { prefix, component }) => {
+            return install::install(&build_dir, &args, prefix, component.as_deref());
+        },
+        Some(Commands::Package) => return install::package(&build_dir, &args, &config),
+        Some(Commands::Coverage) => return coverage::report(&build_dir, &args, &config),
+        Some(Commands::Format { check, since }) => return formatting::run(&args, &config, *check, since.as_deref()),
+        Some(Commands::Log { last, failed }) => return logs::show(&build_dir, *last, *failed),
+        Some(Commands::Cache { command: CacheCommands::Get { name } }) => return cmake_cache::show(&build_dir, name),
+        Some(Commands::Cache { command: CacheCommands::List { filter, all } }) => {
+            return cmake_cache::list(&build_dir, filter.as_deref(), *all);
+        },
+        Some(Commands::Workspace { command: WorkspaceCommands::Build }) => return build_workspace(&args, &config, &build_dir),
+        Some(Commands::Init { .. } | Commands::Config { .. } | Commands::Run { .. } | Commands::Cache { .. }) | None => {},
     }
 
-    if (!build_exists || !args.no_configure) &&
-        !configure(build_dir.as_path(), &args, &config)?.success() {
-            return Err("Configuring failed".into());
+    info!("Using build directory: {}", build_dir.to_string_lossy());
+
+    if args.dry_run {
+        println!("# Build type: {}", args.build_type);
+        println!("# Build directory: {}", build_dir.display());
     }
 
-    if !build(build_dir.as_path(), &args)?.success() {
+    let build_exists = prepare_build_dir(&build_dir, &args, &config)?;
+    configure_if_needed(&build_dir, build_exists, &args, &config, cache_edit.as_ref())?;
+
+    if cache_edit.is_some() {
+        return Ok(());
+    }
+
+    hooks::run("pre_build", &build_dir, &args, &config)?;
+
+    if !build(build_dir.as_path(), &args, &config)?.success() {
         return Err("Build failed".into());
     }
 
-    create_compile_cmd_symlink(build_dir.as_path(), Path::new(&args.project))
+    hooks::run("post_build", &build_dir, &args, &config)?;
+
+    let follow = config.get_bool("compile_commands.follow").unwrap_or(true);
+    create_compile_cmd_symlink(build_dir.as_path(), Path::new(args.project()), follow, args.dry_run)
         .map_err(|e| format!("Failed to create a symlink for `compile_commands.json`: {e}"))?;
 
-    if args.run {
-        run(&args.target, &build_dir, &config, &args)?;
-        info!("Built exectuable has been successfully run.");
+    if let Some(runs) = args.bench {
+        hooks::run("pre_run", &build_dir, &args, &config)?;
+        bench(&args.target, &build_dir, &config, &args, &profile, runs)?;
+        hooks::run("post_run", &build_dir, &args, &config)?;
+    } else if args.run {
+        hooks::run("pre_run", &build_dir, &args, &config)?;
+        run(&args.target, &build_dir, &config, &args, &profile)?;
+
+        if !args.dry_run {
+            info!("Built exectuable has been successfully run.");
+        }
+
+        if args.coverage {
+            coverage::report(&build_dir, &args, &config)?;
+        }
+
+        hooks::run("post_run", &build_dir, &args, &config)?;
     }
 
     Ok(())
@@ -251,8 +924,13 @@ fn main() {
     match entrypoint() {
         Ok(()) => {},
         Err(e) => {
+            if let Some(signal) = process::interrupted() {
+                log::error!("Interrupted: {e}");
+                std::process::exit(128 + signal);
+            }
+
             log::error!("Fatal error encountered: {e}");
-            process::exit(1);
+            std::process::exit(1);
         }
     }
 }
@@ -265,10 +943,12 @@ mod tests {
     fn build_dir_default() {
         assert_eq!(
             BuildPath{
-                project: "project".into(),
-                build_type: "Debug".into(),
-                compiler_path: "".into(),
+                project: "project",
+                build_type: "Debug",
+                compiler_path: "",
+                toolchain: None,
                 sanitizer: None,
+                coverage: false,
                 custom_dir: None
             }.to_path().to_string_lossy(),
             "project/build/debug"
@@ -279,10 +959,12 @@ mod tests {
     fn build_dir_custom() {
         assert_eq!(
             BuildPath{
-                project: "project".into(),
-                build_type: "Debug".into(),
-                compiler_path: "".into(),
+                project: "project",
+                build_type: "Debug",
+                compiler_path: "",
+                toolchain: None,
                 sanitizer: None,
+                coverage: false,
                 custom_dir: "custom".into()
             }.to_path().to_string_lossy(),
             "project/build/custom"
@@ -293,10 +975,12 @@ mod tests {
     fn build_dir_sanitizer() {
         assert_eq!(
             BuildPath{
-                project: "project".into(),
-                build_type: "Debug".into(),
-                compiler_path: "".into(),
+                project: "project",
+                build_type: "Debug",
+                compiler_path: "",
+                toolchain: None,
                 sanitizer: Some("asan"),
+                coverage: false,
                 custom_dir: None
             }.to_path().to_string_lossy(),
             "project/build/debug-asan"
@@ -307,10 +991,12 @@ mod tests {
     fn build_dir_compiler() {
         assert_eq!(
             BuildPath{
-                project: "project".into(),
-                build_type: "Debug".into(),
-                compiler_path: "gcc".into(),
+                project: "project",
+                build_type: "Debug",
+                compiler_path: "gcc",
+                toolchain: None,
                 sanitizer: None,
+                coverage: false,
                 custom_dir: None
             }.to_path().to_string_lossy(),
             "project/build/debug-gcc"
@@ -321,13 +1007,47 @@ mod tests {
     fn build_dir_both() {
         assert_eq!(
             BuildPath{
-                project: "project".into(),
-                build_type: "Debug".into(),
-                compiler_path: "gcc".into(),
+                project: "project",
+                build_type: "Debug",
+                compiler_path: "gcc",
+                toolchain: None,
                 sanitizer: Some("asan"),
+                coverage: false,
                 custom_dir: None
             }.to_path().to_string_lossy(),
             "project/build/debug-gcc-asan"
         );
     }
+
+    #[test]
+    fn build_dir_toolchain() {
+        assert_eq!(
+            BuildPath{
+                project: "project",
+                build_type: "Release",
+                compiler_path: "/usr/bin/aarch64-linux-gnu-g++",
+                toolchain: Some("aarch64-linux-gnu"),
+                sanitizer: Some("asan"),
+                coverage: false,
+                custom_dir: None
+            }.to_path().to_string_lossy(),
+            "project/build/release-aarch64-linux-gnu-g++-aarch64-linux-gnu-asan"
+        );
+    }
+
+    #[test]
+    fn build_dir_coverage() {
+        assert_eq!(
+            BuildPath{
+                project: "project",
+                build_type: "Debug",
+                compiler_path: "clang++",
+                toolchain: None,
+                sanitizer: None,
+                coverage: true,
+                custom_dir: None
+            }.to_path().to_string_lossy(),
+            "project/build/debug-clang++-coverage"
+        );
+    }
 }
@@ -0,0 +1,572 @@
+//! Supervision of child processes: timeouts, resource limits and signals.
+//!
+//! ```yaml
+//! run:
+//!   timeout: 30s
+//!   limits:
+//!     memory: 512M  # address space
+//!     cpu: 60       # CPU time in seconds
+//!     files: 256    # open file descriptors
+//! ```
+//!
+//! Note that sanitizers reserve a huge address space, so a memory limit is not usable with them.
+//!
+//! While waiting for a child, `SIGINT` and `SIGTERM` received by baldr are forwarded to the
+//! child's process group, and baldr reports the interruption after the child has exited. An
+//! interactive child (e.g. a debugger or a hook running `sudo`) stays in the foreground process
+//! group instead, so `Ctrl-C` of the terminal reaches it directly, and only signals sent by other
+//! processes are forwarded to it. On `Ctrl-C` the interruption is only reported if the child is
+//! terminated by it, since it may handle `SIGINT` without exiting.
+//!
+//! A child in its own process group is stopped by the terminal when reading from it, it is killed
+//! then instead of waiting for it forever.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{Child, Command, ExitStatus};
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use config::Config;
+use log::*;
+
+use crate::format_size;
+
+/// Time given to the process to exit after `SIGTERM` before it is killed.
+const KILL_GRACE: Duration = Duration::from_secs(5);
+
+/// Interval of polling the process for exit.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Parse a duration in seconds with an optional `s`, `m`, `h` or `d` suffix.
+///
+/// ```
+/// use std::time::Duration;
+/// use baldr::process::parse_duration;
+///
+/// assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+/// assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+/// assert!(parse_duration("2y").is_err());
+/// assert!(parse_duration("99999999999999999d").is_err());
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the duration is malformed.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => value.split_at(i),
+        None => (value, "s"),
+    };
+
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("Invalid duration `{value}`, expected e.g. `30s`, `5m` or `1h`")),
+    };
+
+    number.parse::<u64>()
+        .map_err(|e| format!("Invalid duration `{value}`: {e}"))?
+        .checked_mul(multiplier)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("Invalid duration `{value}`: it is too large"))
+}
+
+/// Parse a size in bytes with an optional `K`, `M` or `G` suffix (powers of 1024).
+///
+/// ```
+/// use baldr::process::parse_size;
+///
+/// assert_eq!(parse_size("512M"), Ok(512 * 1024 * 1024));
+/// assert_eq!(parse_size("100"), Ok(100));
+/// assert!(parse_size("99999999999999G").is_err());
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the size is malformed.
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => value.split_at(i),
+        None => (value, ""),
+    };
+
+    let multiplier = match unit.to_uppercase().trim_end_matches('B') {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        _ => return Err(format!("Invalid size `{value}`, expected e.g. `512M` or `2G`")),
+    };
+
+    number.parse::<u64>()
+        .map_err(|e| format!("Invalid size `{value}`: {e}"))?
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("Invalid size `{value}`: it is too large"))
+}
+
+/// Available memory in bytes from the content of `/proc/meminfo`.
+///
+/// ```
+/// use baldr::process::parse_meminfo;
+///
+/// assert_eq!(parse_meminfo("MemTotal: 2048 kB\nMemAvailable:    1024 kB\n"), Some(1024 * 1024));
+/// ```
+pub fn parse_meminfo(content: &str) -> Option<u64> {
+    content.lines()
+        .find_map(|x| x.strip_prefix("MemAvailable:"))
+        .and_then(|x| x.trim().strip_suffix("kB"))
+        .and_then(|x| x.trim().parse::<u64>().ok())
+        .map(|x| x * 1024)
+}
+
+/// Memory available for starting new processes without swapping, if it is known.
+pub fn available_memory() -> Option<u64> {
+    fs::read_to_string("/proc/meminfo").ok().as_deref().and_then(parse_meminfo)
+}
+
+/// Resource limits applied to the child process via `setrlimit`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Limits {
+    /// Address space in bytes
+    pub memory: Option<u64>,
+
+    /// CPU time in seconds
+    pub cpu: Option<u64>,
+
+    /// Number of open file descriptors
+    pub files: Option<u64>,
+}
+
+impl Limits {
+    pub fn is_empty(&self) -> bool {
+        *self == Limits::default()
+    }
+
+    /// Limits of `self`, each one falling back to the one of `base` if it is not set.
+    #[must_use]
+    pub fn or(self, base: Limits) -> Limits {
+        Limits {
+            memory: self.memory.or(base.memory),
+            cpu: self.cpu.or(base.cpu),
+            files: self.files.or(base.files),
+        }
+    }
+}
+
+impl fmt::Display for Limits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut limits = Vec::new();
+
+        if let Some(x) = self.memory {
+            limits.push(format!("memory: {}", format_size(x)));
+        }
+        if let Some(x) = self.cpu {
+            limits.push(format!("cpu: {x}s"));
+        }
+        if let Some(x) = self.files {
+            limits.push(format!("files: {x}"));
+        }
+
+        f.write_str(&limits.join(", "))
+    }
+}
+
+/// Read the resource limits configured under `section`.
+///
+/// # Errors
+///
+/// Returns an error if a limit is malformed.
+pub fn get_limits(cfg: &Config, section: &str) -> Result<Limits, String> {
+    let memory = match cfg.get_string(&format!("{section}.limits.memory")) {
+        Ok(x) => Some(parse_size(&x)?),
+        Err(_) => None,
+    };
+
+    let cpu = match cfg.get_string(&format!("{section}.limits.cpu")) {
+        Ok(x) => Some(parse_duration(&x)?.as_secs()),
+        Err(_) => None,
+    };
+
+    let files = match cfg.get_int(&format!("{section}.limits.files")) {
+        Ok(x) => Some(u64::try_from(x).map_err(|e| format!("Invalid open files limit: {e}"))?),
+        Err(_) => None,
+    };
+
+    Ok(Limits { memory, cpu, files })
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type Resource = libc::__rlimit_resource_t;
+
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+type Resource = libc::c_int;
+
+fn setrlimit(resource: Resource, soft: u64, hard: u64) -> io::Result<()> {
+    let limit = libc::rlimit {
+        rlim_cur: soft as libc::rlim_t,
+        rlim_max: hard as libc::rlim_t,
+    };
+
+    // SAFETY: `setrlimit` is async-signal-safe and the pointer is valid for the call.
+    if unsafe { libc::setrlimit(resource, &limit) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// Apply the resource limits to the command, taking effect in the child after forking.
+///
+/// Exceeding the CPU time limit results in `SIGXCPU`, and `SIGKILL` a second later.
+pub fn set_limits(cmd: &mut Command, limits: &Limits) {
+    if limits.is_empty() {
+        return;
+    }
+
+    let limits = limits.clone();
+
+    // SAFETY: only async-signal-safe functions are called between fork and exec.
+    unsafe {
+        cmd.pre_exec(move || {
+            if let Some(x) = limits.memory {
+                setrlimit(libc::RLIMIT_AS, x, x)?;
+            }
+            if let Some(x) = limits.cpu {
+                setrlimit(libc::RLIMIT_CPU, x, x + 1)?;
+            }
+            if let Some(x) = limits.files {
+                setrlimit(libc::RLIMIT_NOFILE, x, x)?;
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Start the command, in its own process group unless it is interactive.
+///
+/// Interactive commands (e.g. a debugger or a program reading the terminal) stay in the
+/// foreground process group, as a background one is stopped when reading the terminal.
+///
+/// # Errors
+///
+/// Returns an error if the process cannot be started.
+pub fn spawn(cmd: &mut Command, interactive: bool) -> io::Result<Child> {
+    if !interactive {
+        cmd.process_group(0);
+    }
+    cmd.spawn()
+}
+
+/// Target of signals sent to the child: its process group (as a negative ID) if it leads one,
+/// otherwise its PID.
+fn signal_target(child: &Child) -> Option<libc::pid_t> {
+    let pid = libc::pid_t::try_from(child.id()).ok()?;
+
+    // SAFETY: plain syscall, the process is not reaped yet, so the PID cannot be reused.
+    if unsafe { libc::getpgid(pid) } == pid {
+        Some(-pid)
+    } else {
+        Some(pid)
+    }
+}
+
+/// Send a signal to the process, or to its process group if it leads one.
+pub fn kill(child: &Child, signal: i32) {
+    let Some(target) = signal_target(child) else {
+        return;
+    };
+
+    // SAFETY: plain syscall, the process is not reaped yet, so the PID cannot be reused.
+    if unsafe { libc::kill(target, signal) } != 0 {
+        debug!("Failed to send {} to {target}: {}", signal_name(signal), io::Error::last_os_error());
+    }
+}
+
+/// Where the received signals are forwarded to, see [`signal_target`], 0 if nowhere.
+static FORWARD_TO: AtomicI32 = AtomicI32::new(0);
+
+/// Last signal received while waiting for a child, 0 if none.
+static INTERRUPTED: AtomicI32 = AtomicI32::new(0);
+
+/// Whether `SIGINT` of the terminal has been received while waiting for the last child.
+static TERMINAL_INTERRUPT: AtomicBool = AtomicBool::new(false);
+
+/// Signals forwarded to the child.
+const FORWARDED_SIGNALS: [libc::c_int; 2] = [libc::SIGINT, libc::SIGTERM];
+
+extern "C" fn forward_signal(signal: libc::c_int, info: *mut libc::siginfo_t, _: *mut libc::c_void) {
+    let target = FORWARD_TO.load(Ordering::SeqCst);
+
+    // SAFETY: the kernel passes a valid `siginfo_t` to `SA_SIGINFO` handlers. Signals sent by
+    // processes have a non-positive code.
+    let from_kernel = !info.is_null() && unsafe { (*info).si_code } > 0;
+
+    // A child in the foreground process group receives `SIGINT` of the terminal by itself, and
+    // decides on its own whether to exit, see `wait`.
+    if target > 0 && signal == libc::SIGINT && from_kernel {
+        TERMINAL_INTERRUPT.store(true, Ordering::SeqCst);
+        return;
+    }
+
+    INTERRUPTED.store(signal, Ordering::SeqCst);
+
+    if target != 0 {
+        // SAFETY: `kill` is async-signal-safe.
+        unsafe { libc::kill(target, signal) };
+    }
+}
+
+/// Forwarding of the signals to a child, the previous handlers are restored when dropped.
+struct Forwarding {
+    previous: Vec<(libc::c_int, libc::sigaction)>,
+}
+
+impl Forwarding {
+    fn new(child: &Child) -> Self {
+        INTERRUPTED.store(0, Ordering::SeqCst);
+        TERMINAL_INTERRUPT.store(false, Ordering::SeqCst);
+        FORWARD_TO.store(signal_target(child).unwrap_or_default(), Ordering::SeqCst);
+
+        let previous = FORWARDED_SIGNALS.iter()
+            .filter_map(|&signal| {
+                // SAFETY: `sigaction` is a plain C struct, all zeros is a valid value.
+                let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+                let mut previous: libc::sigaction = unsafe { std::mem::zeroed() };
+                action.sa_sigaction = forward_signal as *const () as libc::sighandler_t;
+                action.sa_flags = libc::SA_SIGINFO;
+
+                // No `SA_RESTART`, so waiting is interrupted. SAFETY: the pointers are valid for
+                // the call, and the handler only calls async-signal-safe functions.
+                let ret = unsafe {
+                    libc::sigemptyset(&mut action.sa_mask);
+                    libc::sigaction(signal, &action, &mut previous)
+                };
+
+                if ret == 0 {
+                    Some((signal, previous))
+                } else {
+                    debug!("Failed to handle {}: {}", signal_name(signal), io::Error::last_os_error());
+                    None
+                }
+            })
+            .collect();
+
+        Self { previous }
+    }
+}
+
+impl Drop for Forwarding {
+    fn drop(&mut self) {
+        for (signal, previous) in &self.previous {
+            // SAFETY: the previous action was returned by `sigaction`.
+            unsafe { libc::sigaction(*signal, previous, std::ptr::null_mut()) };
+        }
+        FORWARD_TO.store(0, Ordering::SeqCst);
+    }
+}
+
+/// Signal baldr has received while waiting for the last child, if any.
+pub fn interrupted() -> Option<i32> {
+    Some(INTERRUPTED.load(Ordering::SeqCst)).filter(|x| *x != 0)
+}
+
+/// How the supervised process has finished.
+#[derive(Debug)]
+pub struct Outcome {
+    pub status: ExitStatus,
+
+    /// Whether the process had to be stopped due to exceeding the timeout
+    pub timed_out: bool,
+
+    /// Signal baldr has received and forwarded while waiting, e.g. `SIGINT`, see the module
+    /// documentation
+    pub interrupted: Option<i32>,
+
+    /// Wall time from starting to wait until the process has exited
+    pub elapsed: Duration,
+
+    /// Peak resident set size in bytes
+    pub max_rss: u64,
+
+    /// CPU time spent in user and kernel mode
+    pub cpu_time: Duration,
+}
+
+/// Convert a `timeval` of the resource usage.
+fn duration(time: libc::timeval) -> Duration {
+    Duration::from_secs(u64::try_from(time.tv_sec).unwrap_or_default())
+        + Duration::from_micros(u64::try_from(time.tv_usec).unwrap_or_default())
+}
+
+/// Reap the process if it has exited (or wait for it to exit if `block` is set), collecting its
+/// resource usage.
+///
+/// A process stopped by reading or writing the terminal in the background is killed.
+fn wait4(child: &Child, block: bool) -> io::Result<Option<(ExitStatus, libc::rusage)>> {
+    let pid = libc::pid_t::try_from(child.id()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut status = 0;
+
+    // SAFETY: `rusage` is a plain C struct, all zeros is a valid value.
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+
+    let options = libc::WUNTRACED | if block { 0 } else { libc::WNOHANG };
+
+    loop {
+        // SAFETY: plain syscall, the pointers are valid for the duration of the call.
+        let ret = unsafe { libc::wait4(pid, &mut status, options, &mut usage) };
+
+        match ret {
+            0 => return Ok(None),
+            x if x == pid && libc::WIFSTOPPED(status) => {
+                let signal = libc::WSTOPSIG(status);
+                if signal == libc::SIGTTIN || signal == libc::SIGTTOU {
+                    warn!("Process is stopped by {}, it cannot use the terminal in the background, killing it...", signal_name(signal));
+                    kill(child, libc::SIGKILL);
+                }
+            },
+            x if x == pid => return Ok(Some((ExitStatus::from_raw(status), usage))),
+            _ => {
+                let e = io::Error::last_os_error();
+                if e.kind() != io::ErrorKind::Interrupted {
+                    return Err(e);
+                }
+            },
+        }
+    }
+}
+
+/// Wait for the process to exit.
+///
+/// If the process does not exit within the timeout, it is asked to terminate with `SIGTERM`, and
+/// killed with `SIGKILL` if it is still running after a grace period.
+///
+/// `SIGINT` and `SIGTERM` received meanwhile are forwarded to the process, see the module
+/// documentation.
+///
+/// The process is reaped here, so it must not be waited for via [`Child`] afterwards.
+///
+/// # Errors
+///
+/// Returns an error if waiting for the process fails.
+pub fn wait(child: &mut Child, timeout: Option<Duration>) -> io::Result<Outcome> {
+    let _forwarding = Forwarding::new(child);
+    let start = Instant::now();
+    let mut terminated: Option<Instant> = None;
+
+    let outcome = |(status, usage): (ExitStatus, libc::rusage), timed_out| {
+        // An interactive child terminated by `Ctrl-C` counts as an interruption.
+        if TERMINAL_INTERRUPT.load(Ordering::SeqCst) && status.signal() == Some(libc::SIGINT) {
+            INTERRUPTED.store(libc::SIGINT, Ordering::SeqCst);
+        }
+
+        Outcome {
+            status,
+            timed_out,
+            interrupted: interrupted(),
+            elapsed: start.elapsed(),
+            // `ru_maxrss` is in kilobytes on Linux.
+            max_rss: u64::try_from(usage.ru_maxrss).unwrap_or_default() * 1024,
+            cpu_time: duration(usage.ru_utime) + duration(usage.ru_stime),
+        }
+    };
+
+    let Some(timeout) = timeout else {
+        return wait4(child, true)?
+            .map(|x| outcome(x, false))
+            .ok_or_else(|| io::Error::other("Process has not exited"));
+    };
+
+    loop {
+        if let Some(x) = wait4(child, false)? {
+            return Ok(outcome(x, terminated.is_some()));
+        }
+
+        match terminated {
+            None if start.elapsed() >= timeout => {
+                warn!("Timeout of {}s is exceeded, terminating the process...", timeout.as_secs());
+                kill(child, libc::SIGTERM);
+                terminated = Some(Instant::now());
+            },
+            Some(x) if x.elapsed() >= KILL_GRACE => {
+                warn!("Process is still running {}s after `SIGTERM`, killing it...", KILL_GRACE.as_secs());
+                kill(child, libc::SIGKILL);
+                return wait4(child, true)?
+                    .map(|x| outcome(x, true))
+                    .ok_or_else(|| io::Error::other("Process has not exited"));
+            },
+            _ => {},
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Name of a signal, e.g. `SIGSEGV`.
+pub fn signal_name(signal: i32) -> String {
+    let name = match signal {
+        libc::SIGHUP => "SIGHUP",
+        libc::SIGINT => "SIGINT",
+        libc::SIGQUIT => "SIGQUIT",
+        libc::SIGILL => "SIGILL",
+        libc::SIGTRAP => "SIGTRAP",
+        libc::SIGABRT => "SIGABRT",
+        libc::SIGBUS => "SIGBUS",
+        libc::SIGFPE => "SIGFPE",
+        libc::SIGKILL => "SIGKILL",
+        libc::SIGUSR1 => "SIGUSR1",
+        libc::SIGSEGV => "SIGSEGV",
+        libc::SIGUSR2 => "SIGUSR2",
+        libc::SIGPIPE => "SIGPIPE",
+        libc::SIGALRM => "SIGALRM",
+        libc::SIGTERM => "SIGTERM",
+        libc::SIGXCPU => "SIGXCPU",
+        libc::SIGXFSZ => "SIGXFSZ",
+        libc::SIGTTIN => "SIGTTIN",
+        libc::SIGTTOU => "SIGTTOU",
+        _ => return format!("signal {signal}"),
+    };
+
+    name.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeout() {
+        let mut child = Command::new("sleep").arg("10").spawn().unwrap();
+        let outcome = wait(&mut child, Some(Duration::from_millis(100))).unwrap();
+
+        assert!(outcome.timed_out);
+        assert_eq!(std::os::unix::process::ExitStatusExt::signal(&outcome.status), Some(libc::SIGTERM));
+    }
+
+    #[test]
+    fn stopped() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "kill -TTIN $$; sleep 10"]);
+        let mut child = spawn(&mut cmd, false).unwrap();
+        let outcome = wait(&mut child, Some(Duration::from_secs(5))).unwrap();
+
+        assert!(!outcome.timed_out);
+        assert_eq!(std::os::unix::process::ExitStatusExt::signal(&outcome.status), Some(libc::SIGKILL));
+    }
+
+    #[test]
+    fn limits() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "ulimit -n"]);
+        set_limits(&mut cmd, &Limits { files: Some(42), ..Default::default() });
+
+        assert_eq!(String::from_utf8(cmd.output().unwrap().stdout).unwrap(), "42\n");
+    }
+}
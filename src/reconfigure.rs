@@ -0,0 +1,263 @@
+//! Deciding whether to configure, by comparing the inputs of configuring to the ones of the last
+//! successful configure.
+//!
+//! The inputs are the build type, the compilers, the generator (`CMAKE_GENERATOR` of the
+//! environment), the toolchain, the manifest of the dependencies and the CMake definitions. They
+//! are stored in `<build-dir>/.baldr-configure`.
+//!
+//! Some settings cannot be changed in an existing build directory, CMake fails to configure it
+//! instead. These are checked in its cache, see [`cache_mismatches`].
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use config::Config;
+
+use crate::{
+    Args,
+    cmake_cache,
+    deps,
+    find_in_path,
+    fingerprint,
+    get_cc,
+    get_cxx,
+    get_toolchain,
+};
+
+/// File in the build directory storing the inputs of the last successful configure.
+const STAMP: &str = ".baldr-configure";
+
+/// Key of the CMake definitions, given once for each of them.
+const DEFINE: &str = "define";
+
+/// Inputs with a single value, and how they are called in the reason of reconfiguring.
+const SINGLE: [(&str, &str); 6] = [
+    ("build_type", "build type"),
+    ("cc", "C compiler"),
+    ("cxx", "C++ compiler"),
+    ("generator", "generator"),
+    ("toolchain", "toolchain"),
+    ("dependencies", "dependency manifest"),
+];
+
+/// Inputs whose values are fingerprints, and not worth showing.
+const HASHED: [&str; 2] = ["toolchain", "dependencies"];
+
+/// Inputs of configuring as `(key, value)` pairs, see the module documentation.
+///
+/// The definitions are taken from the configure command (see [`crate::configure_cmd`]).
+pub fn inputs(cmd: &Command, args: &Args, cfg: &Config) -> Vec<(String, String)> {
+    let cmd_args = cmd.get_args().map(|x| x.to_string_lossy().into_owned()).collect::<Vec<_>>();
+
+    let generator = env::var("CMAKE_GENERATOR").unwrap_or_default();
+
+    let toolchain = get_toolchain(cfg)
+        .map(|x| if let Some(content) = x.content() {
+            fingerprint(content.as_bytes())
+        } else {
+            let file = x.path(args.project(), Path::new(""));
+            format!("{} {}", file.display(), fingerprint(&fs::read(&file).unwrap_or_default()))
+        })
+        .unwrap_or_default();
+
+    let mut inputs = vec![
+        ("build_type".to_string(), args.build_type.clone()),
+        ("cc".to_string(), get_cc(cfg)),
+        ("cxx".to_string(), get_cxx(cfg)),
+        ("generator".to_string(), generator),
+        ("toolchain".to_string(), toolchain),
+        ("dependencies".to_string(), deps::manifest_fingerprint(args, cfg).unwrap_or_default()),
+    ];
+
+    inputs.extend(
+        cmd_args.iter()
+            .filter_map(|x| x.strip_prefix("-D"))
+            .filter(|x| !x.starts_with("CMAKE_BUILD_TYPE="))
+            .map(|x| (DEFINE.to_string(), x.to_string()))
+    );
+
+    inputs
+}
+
+/// Parse the stored inputs, one `key=value` pair per line.
+fn parse(content: &str) -> Vec<(String, String)> {
+    content.lines()
+        .filter_map(|x| x.split_once('='))
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+/// Inputs of the last successful configure, if known.
+pub fn read(build_dir: &Path) -> Option<Vec<(String, String)>> {
+    fs::read_to_string(build_dir.join(STAMP)).ok().map(|x| parse(&x))
+}
+
+/// Store the inputs of a successful configure.
+///
+/// # Errors
+///
+/// Returns an error if the inputs cannot be written.
+pub fn save(build_dir: &Path, inputs: &[(String, String)]) -> Result<(), String> {
+    let file = build_dir.join(STAMP);
+    let content = inputs.iter()
+        .map(|(k, v)| format!("{k}={v}\n"))
+        .collect::<String>();
+
+    fs::write(&file, content).map_err(|e| format!("Failed to write `{}`: {e}", file.display()))
+}
+
+/// Forget the inputs, so that the next invocation configures even if this one fails.
+pub fn forget(build_dir: &Path) {
+    let _ = fs::remove_file(build_dir.join(STAMP));
+}
+
+/// Describe the differences of the inputs.
+///
+/// ```
+/// use baldr::reconfigure::changes;
+///
+/// let pair = |k: &str, v: &str| (k.to_string(), v.to_string());
+/// let previous = [pair("cxx", "g++"), pair("define", "A=1"), pair("define", "B=2")];
+/// let current = [pair("cxx", "clang++"), pair("define", "A=1"), pair("define", "B=3")];
+///
+/// assert_eq!(
+///     changes(&previous, &current),
+///     ["C++ compiler has changed from `g++` to `clang++`", "definitions have changed: +B=3, -B=2"]
+/// );
+/// ```
+pub fn changes(previous: &[(String, String)], current: &[(String, String)]) -> Vec<String> {
+    let value = |inputs: &[(String, String)], key: &str| inputs.iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.clone())
+        .unwrap_or_default();
+
+    let defines = |inputs: &[(String, String)]| inputs.iter()
+        .filter(|(k, _)| k == DEFINE)
+        .map(|(_, v)| v.clone())
+        .collect::<Vec<_>>();
+
+    let mut changes = Vec::new();
+
+    for (key, label) in SINGLE {
+        let (before, after) = (value(previous, key), value(current, key));
+        if before == after {
+            continue;
+        }
+
+        if HASHED.contains(&key) {
+            changes.push(format!("{label} has changed"));
+        } else {
+            let show = |x: &str| if x.is_empty() { "(default)".to_string() } else { format!("`{x}`") };
+            changes.push(format!("{label} has changed from {} to {}", show(&before), show(&after)));
+        }
+    }
+
+    let (before, after) = (defines(previous), defines(current));
+    let diff = after.iter().filter(|x| !before.contains(x)).map(|x| format!("+{x}"))
+        .chain(before.iter().filter(|x| !after.contains(x)).map(|x| format!("-{x}")))
+        .collect::<Vec<_>>();
+
+    if !diff.is_empty() {
+        changes.push(format!("definitions have changed: {}", diff.join(", ")));
+    }
+
+    changes
+}
+
+/// Why configuring is needed, `None` if the inputs are the same as at the last successful
+/// configure.
+pub fn reason(build_dir: &Path, current: &[(String, String)]) -> Option<String> {
+    let Some(previous) = read(build_dir) else {
+        return Some("there is no record of a successful configure".into());
+    };
+
+    let changes = changes(&previous, current);
+    (!changes.is_empty()).then(|| changes.join("; "))
+}
+
+/// Resolve a compiler given by name or path to its canonical path.
+fn resolve_compiler(compiler: &str) -> Option<PathBuf> {
+    let path = if compiler.contains('/') {
+        PathBuf::from(compiler)
+    } else {
+        find_in_path(compiler)?
+    };
+
+    fs::canonicalize(path).ok()
+}
+
+/// Compare the cache of an existing build directory with the requested settings that CMake
+/// cannot change in place: the C++ compiler, the generator and the source directory.
+///
+/// Settings that are not requested explicitly or cannot be resolved are not compared. Returns the
+/// description of each mismatch, empty if the build directory has no cache.
+pub fn cache_mismatches(build_dir: &Path, args: &Args, cfg: &Config) -> Vec<String> {
+    let Ok(cache) = cmake_cache::read(build_dir) else {
+        return Vec::new();
+    };
+
+    let mut mismatches = Vec::new();
+    let mut compare = |name: &str, cached: Option<&str>, requested: &str, same: bool| {
+        if !same {
+            mismatches.push(format!("`{name}` is `{}`, but `{requested}` is requested", cached.unwrap_or_default()));
+        }
+    };
+
+    // Compilers are only given to CMake as a pair, see `configure_cmd`.
+    let cxx = get_cxx(cfg);
+    if !get_cc(cfg).is_empty() && !cxx.is_empty() {
+        let cached = cmake_cache::get(&cache, "CMAKE_CXX_COMPILER");
+        if let (Some(requested), Some(cached_path)) = (resolve_compiler(&cxx), cached.and_then(resolve_compiler)) {
+            compare("CMAKE_CXX_COMPILER", cached, &cxx, requested == cached_path);
+        }
+    }
+
+    if let Ok(generator) = env::var("CMAKE_GENERATOR") {
+        let cached = cmake_cache::get(&cache, "CMAKE_GENERATOR");
+        if cached.is_some() {
+            compare("CMAKE_GENERATOR", cached, &generator, cached == Some(generator.as_str()));
+        }
+    }
+
+    let cached = cmake_cache::get(&cache, "CMAKE_HOME_DIRECTORY");
+    if let (Ok(requested), Some(cached_path)) = (fs::canonicalize(args.project()), cached) {
+        let same = fs::canonicalize(cached_path).is_ok_and(|x| x == requested);
+        compare("CMAKE_HOME_DIRECTORY", cached, &requested.to_string_lossy(), same);
+    }
+
+    mismatches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stored_inputs() {
+        let dir = std::env::temp_dir().join(format!("baldr-reconfigure-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let inputs = vec![
+            ("build_type".to_string(), "Debug".to_string()),
+            ("generator".to_string(), String::new()),
+            ("define".to_string(), "FLAGS=-O2 -g".to_string()),
+        ];
+
+        assert!(reason(&dir, &inputs).is_some());
+
+        save(&dir, &inputs).unwrap();
+        assert_eq!(read(&dir).unwrap(), inputs);
+        assert_eq!(reason(&dir, &inputs), None);
+
+        let mut changed = inputs.clone();
+        changed[1].1 = "Ninja".into();
+        assert_eq!(reason(&dir, &changed).unwrap(), "generator has changed from (default) to `Ninja`");
+
+        forget(&dir);
+        assert!(read(&dir).is_none());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,186 @@
+//! Inspecting the effective configuration and the layers it is merged from.
+
+use config::{Config, Map, Source, Value, ValueKind};
+
+use crate::{Layer, merge_layers};
+
+/// Format a scalar value, or an empty string for tables and arrays.
+fn format_scalar(value: &Value) -> String {
+    match &value.kind {
+        ValueKind::Nil => "null".into(),
+        ValueKind::String(x) if x.is_empty() || x.contains([':', '#', '\n']) || x.trim() != x => format!("{x:?}"),
+        ValueKind::Table(_) | ValueKind::Array(_) => String::new(),
+        _ => value.to_string(),
+    }
+}
+
+fn format_table(table: &Map<String, Value>, indent: usize, out: &mut String) {
+    let mut keys = table.keys().collect::<Vec<_>>();
+    keys.sort();
+
+    for key in keys {
+        let value = &table[key];
+        match &value.kind {
+            ValueKind::Table(x) if x.is_empty() => out.push_str(&format!("{:indent$}{key}: {{}}\n", "")),
+            ValueKind::Array(x) if x.is_empty() => out.push_str(&format!("{:indent$}{key}: []\n", "")),
+            ValueKind::Table(x) => {
+                out.push_str(&format!("{:indent$}{key}:\n", ""));
+                format_table(x, indent + 2, out);
+            },
+            ValueKind::Array(x) => {
+                out.push_str(&format!("{:indent$}{key}:\n", ""));
+                format_array(x, indent + 2, out);
+            },
+            _ => out.push_str(&format!("{:indent$}{key}: {}\n", "", format_scalar(value))),
+        }
+    }
+}
+
+fn format_array(array: &[Value], indent: usize, out: &mut String) {
+    for value in array {
+        match &value.kind {
+            ValueKind::Table(x) => {
+                out.push_str(&format!("{:indent$}-\n", ""));
+                format_table(x, indent + 2, out);
+            },
+            ValueKind::Array(x) => {
+                out.push_str(&format!("{:indent$}-\n", ""));
+                format_array(x, indent + 2, out);
+            },
+            _ => out.push_str(&format!("{:indent$}- {}\n", "", format_scalar(value))),
+        }
+    }
+}
+
+/// Format a value in a YAML-like way with sorted keys, nested values are indented.
+///
+/// ```
+/// use baldr::settings::format_value;
+///
+/// let cfg = config::Config::builder()
+///     .set_override("compiler.cxx", "g++").unwrap()
+///     .set_override("debugger", "gdb").unwrap()
+///     .build().unwrap();
+///
+/// let value = cfg.get::<config::Value>("compiler").unwrap();
+/// assert_eq!(format_value(&value), "cxx: g++\n");
+/// ```
+pub fn format_value(value: &Value) -> String {
+    let mut out = String::new();
+
+    match &value.kind {
+        ValueKind::Table(x) => format_table(x, 0, &mut out),
+        ValueKind::Array(x) => format_array(x, 0, &mut out),
+        _ => out = format_scalar(value) + "\n",
+    }
+
+    out
+}
+
+/// Print the merged configuration.
+///
+/// # Errors
+///
+/// Returns an error if the merged configuration is invalid.
+pub fn show(layers: &[Layer]) -> Result<(), String> {
+    let config = merge_layers(layers).map_err(|e| e.to_string())?;
+    let table = config.collect().map_err(|e| e.to_string())?;
+
+    for layer in layers {
+        println!("# {}", layer.label);
+    }
+
+    print!("{}", format_value(&Value::from(table)));
+    Ok(())
+}
+
+/// Value of the key in the configuration, if it is set.
+fn lookup(config: &Config, key: &str) -> Option<Value> {
+    config.get::<Value>(key).ok()
+}
+
+/// Print each layer setting the key from the lowest priority to the highest, and the effective
+/// value.
+///
+/// # Errors
+///
+/// Returns an error if the key is not set by any layer, or the merged configuration is invalid.
+pub fn explain(layers: &[Layer], key: &str) -> Result<(), String> {
+    // Keys are case-insensitive.
+    let key = key.to_lowercase();
+
+    let setters = layers.iter()
+        .filter_map(|layer| lookup(&layer.config, &key).map(|value| (layer, value)))
+        .collect::<Vec<_>>();
+
+    if setters.is_empty() {
+        return Err(format!("`{key}` is not set in any configuration"));
+    }
+
+    for (layer, value) in &setters {
+        print_value(&layer.label, value);
+    }
+
+    let config = merge_layers(layers).map_err(|e| e.to_string())?;
+    if let Some(value) = lookup(&config, &key) {
+        print_value("effective", &value);
+    }
+
+    Ok(())
+}
+
+fn print_value(label: &str, value: &Value) {
+    match &value.kind {
+        ValueKind::Table(_) | ValueKind::Array(_) => {
+            println!("{label}:");
+            for line in format_value(value).lines() {
+                println!("  {line}");
+            }
+        },
+        _ => println!("{label}: {}", format_scalar(value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(label: &str, yaml: &str) -> Layer {
+        Layer {
+            label: label.into(),
+            config: Config::builder()
+                .add_source(config::File::from_str(yaml, config::FileFormat::Yaml))
+                .build()
+                .unwrap(),
+        }
+    }
+
+    #[test]
+    fn pretty_print() {
+        let layers = [layer("project", "run:\n  env:\n    - A=1\n    - B=x:y\n  cwd: data\ndebugger: gdb\n")];
+        let config = merge_layers(&layers).unwrap();
+
+        assert_eq!(
+            format_value(&Value::from(config.collect().unwrap())),
+            "debugger: gdb\nrun:\n  cwd: data\n  env:\n    - A=1\n    - \"B=x:y\"\n"
+        );
+    }
+
+    #[test]
+    fn layers_setting_a_key() {
+        let layers = [
+            layer("HOME", "compiler:\n  cxx: g++\n"),
+            layer("project", "debugger: gdb\n"),
+            layer("env", "compiler:\n  cxx: clang++\n"),
+        ];
+
+        let setters = layers.iter()
+            .filter(|x| lookup(&x.config, "compiler.cxx").is_some())
+            .map(|x| x.label.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(setters, ["HOME", "env"]);
+        assert!(explain(&layers, "compiler.cxx").is_ok());
+        assert!(explain(&layers, "compiler.cc").is_err());
+    }
+}
@@ -0,0 +1,276 @@
+//! Static analysis with `clang-tidy` over the compilation database.
+//!
+//! Checks are configured by the usual `.clang-tidy` files, which `clang-tidy` looks up itself.
+//!
+//! ```yaml
+//! tidy:
+//!   binary: clang-tidy-18
+//!   # paths relative to the project, `*` and `?` wildcards
+//!   exclude:
+//!     - generated/*
+//! ```
+
+use std::collections::{BTreeSet, VecDeque};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use config::Config;
+use log::*;
+
+use crate::{
+    Args,
+    dirs,
+    execute,
+    format_cmd,
+    get_list,
+    git,
+};
+
+/// Third-party code is not ours to fix.
+const DEFAULT_EXCLUDES: [&str; 6] = [
+    "third_party/*",
+    "third-party/*",
+    "3rdparty/*",
+    "external/*",
+    "extern/*",
+    "vendor/*",
+];
+
+/// Translation units of the compilation database with canonical paths (as far as they exist),
+/// without duplicates.
+///
+/// # Errors
+///
+/// Returns an error if the compilation database cannot be read or parsed.
+pub fn translation_units(build_dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let file = build_dir.join("compile_commands.json");
+    let content = fs::read_to_string(&file)
+        .map_err(|e| format!("Failed to read `{}`, is the project configured? {e}", file.display()))?;
+
+    let json: serde_json::Value = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse `{}`: {e}", file.display()))?;
+
+    let units = json.as_array()
+        .ok_or_else(|| format!("`{}` is not a list of commands", file.display()))?
+        .iter()
+        .filter_map(|entry| {
+            let file = Path::new(entry.get("file")?.as_str()?);
+            let directory = Path::new(entry.get("directory").and_then(|x| x.as_str()).unwrap_or_default());
+            let unit = directory.join(file);
+            Some(fs::canonicalize(&unit).unwrap_or(unit))
+        })
+        .collect::<BTreeSet<_>>();
+
+    Ok(units.into_iter().collect())
+}
+
+/// Keep the files of the project excluding the build directories and the excluded paths.
+pub fn filter_units(units: Vec<PathBuf>, project: &Path, excludes: &[String]) -> Vec<PathBuf> {
+    units.into_iter()
+        .filter(|unit| {
+            let Ok(relative) = unit.strip_prefix(project) else {
+                return false;
+            };
+
+            let relative = relative.to_string_lossy();
+            !relative.starts_with("build/") && !excludes.iter().any(|x| dirs::matches(x, &relative))
+        })
+        .collect()
+}
+
+/// Findings of `clang-tidy` aggregated over all translation units.
+#[derive(Debug, Default)]
+pub struct Summary {
+    /// Diagnostics are collected into a set, as headers are reported for every translation unit
+    /// including them.
+    pub warnings: BTreeSet<String>,
+    pub errors: BTreeSet<String>,
+
+    /// Translation units for which `clang-tidy` has failed
+    pub failed: Vec<PathBuf>,
+}
+
+impl Summary {
+    /// Collect the diagnostics from the output of `clang-tidy`, i.e. lines like
+    /// `<FILE>:<LINE>:<COLUMN>: warning: <MESSAGE> [<CHECK>]`.
+    pub fn collect(&mut self, output: &str) {
+        for line in output.lines() {
+            if line.contains(": warning: ") {
+                self.warnings.insert(line.to_string());
+            } else if line.contains(": error: ") {
+                self.errors.insert(line.to_string());
+            }
+        }
+    }
+}
+
+fn tidy_cmd(binary: &str, build_dir: &Path, unit: &Path, fix: bool) -> Command {
+    let mut cmd = Command::new(binary);
+    cmd.arg("-p").arg(build_dir);
+    cmd.arg("--quiet");
+
+    if fix {
+        cmd.arg("--fix");
+    }
+
+    cmd.arg(unit);
+    cmd
+}
+
+/// Run `clang-tidy` in parallel over the translation units of the project.
+///
+/// Fixes are applied one translation unit at a time, as they might touch the same headers.
+///
+/// # Errors
+///
+/// Returns an error if the compilation database cannot be read, changed files cannot be queried
+/// from git, or `clang-tidy` has found errors or failed.
+pub fn run(build_dir: &Path, args: &Args, config: &Config, since: Option<&str>, fix: bool) -> Result<(), String> {
+    let project = fs::canonicalize(args.project())
+        .map_err(|e| format!("Invalid project directory `{}`: {e}", args.project()))?;
+
+    let mut excludes = get_list(config, "tidy.exclude");
+    excludes.extend(DEFAULT_EXCLUDES.map(String::from));
+
+    let mut units = filter_units(translation_units(build_dir)?, &project, &excludes);
+
+    if units.is_empty() {
+        return Err(format!("No translation units of `{}` are found in the compilation database", project.display()));
+    }
+
+    if let Some(reference) = since {
+        let changed = git::changed_files(&project, reference)?;
+        units.retain(|x| changed.contains(x));
+        info!("{} translation unit(s) changed since `{reference}`.", units.len());
+    }
+
+    let binary = config.get_string("tidy.binary").unwrap_or_else(|_| "clang-tidy".into());
+
+    if args.dry_run {
+        for unit in &units {
+            execute(&mut tidy_cmd(&binary, build_dir, unit, fix), true)?;
+        }
+        return Ok(());
+    }
+
+    let jobs = if fix { 1 } else { usize::try_from(args.jobs()).unwrap_or(1).max(1) };
+    info!("Running `{binary}` on {} translation unit(s) with {jobs} job(s)...", units.len());
+
+    let total = units.len();
+    let queue = Arc::new(Mutex::new(VecDeque::from(units)));
+    let summary = Arc::new(Mutex::new(Summary::default()));
+
+    let workers = (0..jobs).map(|_| {
+        let queue = Arc::clone(&queue);
+        let summary = Arc::clone(&summary);
+        let binary = binary.clone();
+        let build_dir = build_dir.to_path_buf();
+
+        thread::spawn(move || while let Some(unit) = queue.lock().ok().and_then(|mut x| x.pop_front()) {
+            let mut cmd = tidy_cmd(&binary, &build_dir, &unit, fix);
+            debug!("CMD: {}", format_cmd(&cmd));
+            let output = cmd.output();
+
+            // Holding the lock while printing keeps the output of translation units together.
+            let Ok(mut summary) = summary.lock() else {
+                break;
+            };
+
+            match output {
+                Ok(output) => {
+                    let stdout = String::from_utf8_lossy(&output.stdout);
+                    let _ = io::stdout().lock().write_all(stdout.as_bytes());
+                    summary.collect(&stdout);
+
+                    if !output.status.success() {
+                        let _ = io::stderr().lock().write_all(&output.stderr);
+                        summary.failed.push(unit);
+                    }
+                },
+                Err(e) => {
+                    error!("Failed to run `{binary}` on `{}`: {e}", unit.display());
+                    summary.failed.push(unit);
+                },
+            }
+        })
+    }).collect::<Vec<_>>();
+
+    for worker in workers {
+        worker.join().map_err(|_| "clang-tidy worker has panicked".to_string())?;
+    }
+
+    let summary = summary.lock().map_err(|_| "clang-tidy worker has panicked".to_string())?;
+
+    println!(
+        "clang-tidy: {total} translation unit(s) checked, {} warning(s), {} error(s)",
+        summary.warnings.len(),
+        summary.errors.len(),
+    );
+
+    if !summary.errors.is_empty() || !summary.failed.is_empty() {
+        return Err(format!(
+            "clang-tidy has found {} error(s) and failed on {} translation unit(s)",
+            summary.errors.len(),
+            summary.failed.len(),
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter() {
+        let units = [
+            "/project/src/main.cc",
+            "/project/build/debug/generated.cc",
+            "/project/third_party/lib/lib.cc",
+            "/project/generated/x.cc",
+            "/elsewhere/x.cc",
+        ].map(PathBuf::from).to_vec();
+
+        let mut excludes = vec!["generated/*".to_string()];
+        excludes.extend(DEFAULT_EXCLUDES.map(String::from));
+
+        assert_eq!(
+            filter_units(units, Path::new("/project"), &excludes),
+            vec![PathBuf::from("/project/src/main.cc")]
+        );
+    }
+
+    #[test]
+    fn canonical_units() {
+        let dir = std::env::temp_dir().join(format!("baldr-tidy-{}", std::process::id()));
+        let project = dir.join("project");
+        fs::create_dir_all(project.join("src")).unwrap();
+        fs::create_dir_all(project.join("build")).unwrap();
+        fs::write(project.join("src/main.cc"), "").unwrap();
+        std::os::unix::fs::symlink(&project, dir.join("link")).unwrap();
+
+        let database = format!(r#"[{{"directory": "{}/link/build", "file": "../src/main.cc"}}]"#, dir.display());
+        fs::write(project.join("build/compile_commands.json"), database).unwrap();
+
+        let project = fs::canonicalize(project).unwrap();
+        let units = translation_units(&project.join("build")).unwrap();
+        assert_eq!(filter_units(units, &project, &[]), vec![project.join("src/main.cc")]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn summary() {
+        let mut summary = Summary::default();
+        summary.collect("a.h:1:2: warning: use auto [modernize-use-auto]\nb.cc:3:4: error: unknown type 'x' [clang-diagnostic-error]\n");
+        summary.collect("a.h:1:2: warning: use auto [modernize-use-auto]\n    int x = 1;\n");
+
+        assert_eq!(summary.warnings.len(), 1);
+        assert_eq!(summary.errors.len(), 1);
+    }
+}
@@ -0,0 +1,197 @@
+//! Workspaces of interdependent CMake projects, built in dependency order.
+//!
+//! ```yaml
+//! workspace:
+//!   staging: build/staging  # optional, relative to the workspace
+//!   members:
+//!     core:
+//!       path: libs/core
+//!     plugins:
+//!       path: plugins
+//!       depends: [core]
+//!     app:
+//!       path: apps/app
+//!       depends: [core, plugins]
+//! ```
+//!
+//! `baldr workspace build` configures, builds and installs each member into the shared staging
+//! prefix. Members depending on others find them there via `CMAKE_PREFIX_PATH`, the staging prefix
+//! is prepended to a configured one. Each member is built with its own configuration, as if baldr
+//! was run in its directory, in its own build directory (so `--build-dir` cannot be absolute).
+
+use std::path::{Path, PathBuf};
+
+use config::Config;
+
+use crate::{
+    definition_name,
+    get_list,
+};
+
+/// A project of the workspace.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Member {
+    pub name: String,
+
+    /// Directory of the project, relative paths are resolved from the workspace
+    pub path: PathBuf,
+
+    /// Names of the members this one depends on
+    pub depends: Vec<String>,
+}
+
+/// Read the members of the workspace in the order of their names.
+///
+/// # Errors
+///
+/// Returns an error if a member has no path.
+pub fn get_members(cfg: &Config, root: &Path) -> Result<Vec<Member>, String> {
+    let mut names = cfg.get_table("workspace.members")
+        .unwrap_or_default()
+        .into_keys()
+        .collect::<Vec<_>>();
+    names.sort();
+
+    names.into_iter()
+        .map(|name| {
+            let path = cfg.get_string(&format!("workspace.members.{name}.path"))
+                .map_err(|_| format!("Workspace member `{name}` has no `path`"))?;
+
+            Ok(Member {
+                path: root.join(path),
+                depends: get_list(cfg, &format!("workspace.members.{name}.depends")),
+                name,
+            })
+        })
+        .collect()
+}
+
+/// Order the members, so that each one comes after the ones it depends on.
+///
+/// Independent members keep their order.
+///
+/// ```
+/// use std::path::PathBuf;
+/// use baldr::workspace::{Member, order};
+///
+/// let member = |name: &str, depends: &[&str]| Member {
+///     name: name.into(),
+///     path: PathBuf::from(name),
+///     depends: depends.iter().map(|x| x.to_string()).collect(),
+/// };
+///
+/// let ordered = order(vec![member("app", &["core", "plugins"]), member("core", &[]), member("plugins", &["core"])]).unwrap();
+/// let names = ordered.iter().map(|x| x.name.as_str()).collect::<Vec<_>>();
+///
+/// assert_eq!(names, ["core", "plugins", "app"]);
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if a member depends on an unknown one, or the dependencies are circular.
+pub fn order(members: Vec<Member>) -> Result<Vec<Member>, String> {
+    for member in &members {
+        if let Some(unknown) = member.depends.iter().find(|x| !members.iter().any(|m| &m.name == *x)) {
+            return Err(format!("Workspace member `{}` depends on unknown member `{unknown}`", member.name));
+        }
+    }
+
+    let mut ordered: Vec<Member> = Vec::new();
+    let mut pending = members;
+
+    while !pending.is_empty() {
+        let ready = pending.iter()
+            .position(|x| x.depends.iter().all(|d| ordered.iter().any(|m| &m.name == d)));
+
+        let Some(i) = ready else {
+            let names = pending.iter().map(|x| format!("`{}`", x.name)).collect::<Vec<_>>();
+            return Err(format!("Workspace members have circular dependencies: {}", names.join(", ")));
+        };
+
+        ordered.push(pending.remove(i));
+    }
+
+    Ok(ordered)
+}
+
+/// Staging prefix the members are installed into, `workspace.staging` or the default.
+pub fn staging_dir(cfg: &Config, root: &Path, default: &Path) -> PathBuf {
+    cfg.get_string("workspace.staging")
+        .map_or_else(|_| default.to_path_buf(), |x| root.join(x))
+}
+
+/// Prepend the prefixes to `CMAKE_PREFIX_PATH` in the CMake definitions.
+///
+/// Prefixes already defined (e.g. by the user) are kept after them, so that neither of them is
+/// lost.
+///
+/// ```
+/// use baldr::workspace::add_prefix_paths;
+///
+/// let mut definitions = vec!["CMAKE_PREFIX_PATH:PATH=/opt/qt".to_string()];
+/// add_prefix_paths(&mut definitions, &["/staging".into()]);
+///
+/// assert_eq!(definitions, ["CMAKE_PREFIX_PATH:PATH=/staging;/opt/qt"]);
+/// ```
+pub fn add_prefix_paths(definitions: &mut Vec<String>, prefixes: &[String]) {
+    if prefixes.is_empty() {
+        return;
+    }
+
+    let prefixes = prefixes.join(";");
+    let mut defined = false;
+
+    for definition in definitions.iter_mut().filter(|x| definition_name(x) == "CMAKE_PREFIX_PATH") {
+        if let Some((name, value)) = definition.split_once('=') {
+            *definition = if value.is_empty() {
+                format!("{name}={prefixes}")
+            } else {
+                format!("{name}={prefixes};{value}")
+            };
+            defined = true;
+        }
+    }
+
+    if !defined {
+        definitions.push(format!("CMAKE_PREFIX_PATH={prefixes}"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn members() {
+        let cfg = Config::builder()
+            .set_override("workspace.members.core.path", "libs/core").unwrap()
+            .set_override("workspace.members.app.path", "/apps/app").unwrap()
+            .set_override("workspace.members.app.depends", vec!["core"]).unwrap()
+            .build().unwrap();
+
+        let members = get_members(&cfg, Path::new("root")).unwrap();
+        assert_eq!(members[0], Member { name: "app".into(), path: "/apps/app".into(), depends: vec!["core".into()] });
+        assert_eq!(members[1].path, Path::new("root/libs/core"));
+
+        assert_eq!(order(members).unwrap()[0].name, "core");
+        assert_eq!(staging_dir(&cfg, Path::new("root"), Path::new("default")), Path::new("default"));
+    }
+
+    #[test]
+    fn invalid_dependencies() {
+        let member = |name: &str, depends: &[&str]| Member {
+            name: name.into(),
+            path: PathBuf::from(name),
+            depends: depends.iter().map(|x| x.to_string()).collect(),
+        };
+
+        assert_eq!(
+            order(vec![member("a", &["b"]), member("b", &["a"]), member("c", &[])]).unwrap_err(),
+            "Workspace members have circular dependencies: `a`, `b`"
+        );
+        assert_eq!(
+            order(vec![member("a", &["x"])]).unwrap_err(),
+            "Workspace member `a` depends on unknown member `x`"
+        );
+    }
+}
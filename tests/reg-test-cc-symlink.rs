@@ -10,7 +10,7 @@ type AnyError = Result<(), Box<dyn std::error::Error>>;
 const TARGET: &str = "tests/cpp/compile_commands.json";
 
 fn command() -> Command {
-    let mut cmd = Command::cargo_bin(assert_cmd::crate_name!()).unwrap();
+    let mut cmd = Command::cargo_bin(assert_cmd::pkg_name!()).expect("Binary is not built");
 
     cmd.args([
         "--project", "./tests/cpp",
@@ -46,3 +46,45 @@ fn compile_commands_symlink_broken() -> AnyError {
     std::fs::remove_file(target).unwrap();
     Ok(())
 }
+
+#[test]
+fn compile_commands_symlink_follows_build() -> AnyError {
+    command().assert().success();
+    command().args(["--build-type", "Release"]).assert().success();
+
+    assert!(fs::read_link(Path::new(TARGET))?.ends_with("build/release-g++/compile_commands.json"));
+    Ok(())
+}
+
+#[test]
+fn use_build_dir() -> AnyError {
+    let build_dir = Path::new("tests/cpp/build/use-test");
+    fs::create_dir_all(build_dir)?;
+    fs::write(build_dir.join("compile_commands.json"), "[]")?;
+
+    Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["--project", "./tests/cpp", "use", "use-test"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("clangd now uses `./tests/cpp/build/use-test`"))
+    ;
+
+    assert!(fs::read_link(Path::new(TARGET))?.ends_with("build/use-test/compile_commands.json"));
+
+    for args in [&["use", "use-te"][..], &["--build-dir", "use-test", "use"]] {
+        fs::remove_file(TARGET)?;
+
+        Command::cargo_bin(assert_cmd::pkg_name!())?
+            .args(["--project", "./tests/cpp"])
+            .args(args)
+            .assert()
+            .success()
+        ;
+
+        assert!(fs::read_link(Path::new(TARGET))?.ends_with("build/use-test/compile_commands.json"));
+    }
+
+    fs::remove_dir_all(build_dir)?;
+    fs::remove_file(TARGET)?;
+    Ok(())
+}
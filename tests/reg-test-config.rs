@@ -0,0 +1,100 @@
+use std::fs;
+use std::process::Command;
+
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+
+fn command() -> Command {
+    let mut cmd = Command::cargo_bin(assert_cmd::pkg_name!()).expect("Binary is not built");
+    cmd.args(["--project", "./tests/cpp", "config"]);
+    cmd.env_remove("BALDR_DEBUGGER");
+    cmd
+}
+
+#[test]
+fn show() {
+    command()
+        .arg("show")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("compiler:\n  cc: gcc\n  cxx: g++\n"))
+        .stdout(predicate::str::contains("    - CFG1=cfg1\n"))
+    ;
+}
+
+#[test]
+fn explain() {
+    command()
+        .args(["explain", "debugger"])
+        .env("BALDR_DEBUGGER", "lldb")
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"(?s)project \(.*\.baldr\.yaml\): gdb\nenvironment \(BALDR_\*\): lldb\neffective: lldb")
+            .expect("Invalid regex"))
+    ;
+
+    command()
+        .args(["explain", "compiler.linker"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("`compiler.linker` is not set in any configuration"))
+    ;
+}
+
+#[test]
+fn layers() -> Result<(), Box<dyn std::error::Error>> {
+    let root = std::env::temp_dir().join(format!("baldr-config-layers-{}", std::process::id()));
+    let home = root.join("home");
+    let project = root.join("project");
+
+    fs::create_dir_all(home.join(".config").join("baldr"))?;
+    fs::create_dir_all(&project)?;
+    fs::write(home.join(".baldr.yaml"), "debugger: lldb\nbench:\n  warmup: 3\n")?;
+    fs::write(home.join(".config").join("baldr").join("config.yaml"), "debugger: gdb\n")?;
+    fs::write(project.join(".baldr.yaml"), "compiler:\n  cxx: g++\n")?;
+    fs::write(project.join(".baldr.local.yaml"), "compiler:\n  cxx: clang++\n")?;
+
+    let mut cmd = Command::cargo_bin(assert_cmd::pkg_name!())?;
+    cmd.current_dir(&project)
+        .env("HOME", &home)
+        .env("RUST_LOG", "warn")
+        .env_remove("XDG_CONFIG_HOME")
+        .env_remove("BALDR_DEBUGGER")
+        .args(["config", "show"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("cxx: clang++\n"))
+        .stdout(predicate::str::contains("debugger: gdb\n"))
+        .stdout(predicate::str::contains("warmup: 3\n"))
+        .stderr(predicate::str::contains("is deprecated, move it to"))
+    ;
+
+    fs::remove_dir_all(root)?;
+    Ok(())
+}
+
+#[test]
+fn legacy_priority() -> Result<(), Box<dyn std::error::Error>> {
+    let root = std::env::temp_dir().join(format!("baldr-config-legacy-{}", std::process::id()));
+    let home = root.join("home");
+    let xdg = root.join("xdg");
+
+    fs::create_dir_all(&home)?;
+    fs::create_dir_all(&xdg)?;
+    fs::write(home.join(".baldr.yaml"), "debugger: lldb\n")?;
+    fs::write(xdg.join(".baldr.yaml"), "debugger: gdb\n")?;
+
+    let mut cmd = Command::cargo_bin(assert_cmd::pkg_name!())?;
+    cmd.current_dir(&root)
+        .env("HOME", &home)
+        .env("XDG_CONFIG_HOME", &xdg)
+        .env_remove("BALDR_DEBUGGER")
+        .args(["--project", ".", "config", "explain", "debugger"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("effective: lldb"))
+    ;
+
+    fs::remove_dir_all(root)?;
+    Ok(())
+}
@@ -7,7 +7,7 @@ type AnyError = Result<(), Box<dyn std::error::Error>>;
 
 #[test]
 fn configure_failure() -> AnyError {
-    let mut cmd = Command::cargo_bin(assert_cmd::crate_name!())?;
+    let mut cmd = Command::cargo_bin(assert_cmd::pkg_name!())?;
 
     cmd.args([
         "--project", "./tests/cpp",
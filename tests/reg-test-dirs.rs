@@ -0,0 +1,51 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+
+type AnyError = Result<(), Box<dyn std::error::Error>>;
+
+const BUILD_DIR: &str = "./tests/cpp/build/dirs-test";
+
+fn command() -> Command {
+    let mut cmd = Command::cargo_bin(assert_cmd::pkg_name!()).expect("Binary is not built");
+    cmd.args(["--project", "./tests/cpp", "dirs"]);
+    cmd
+}
+
+#[test]
+fn list_and_prune() -> AnyError {
+    fs::create_dir_all(BUILD_DIR)?;
+    fs::write(
+        Path::new(BUILD_DIR).join("CMakeCache.txt"),
+        "CMAKE_BUILD_TYPE:STRING=RelWithDebInfo\nCMAKE_GENERATOR:INTERNAL=Ninja\n"
+    )?;
+
+    command().assert()
+        .success()
+        .stdout(predicate::str::is_match("dirs-test +RelWithDebInfo +Ninja")?)
+    ;
+
+    command()
+        .args(["--prune", "--match", "dirs-t*", "--no-confirm"])
+        .assert()
+        .success()
+    ;
+
+    assert!(!Path::new(BUILD_DIR).exists());
+    Ok(())
+}
+
+#[test]
+fn prune_without_selector() -> AnyError {
+    command()
+        .arg("--prune")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Pruning requires `--older-than` or `--match`"))
+    ;
+
+    Ok(())
+}
@@ -0,0 +1,237 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+
+type AnyError = Result<(), Box<dyn std::error::Error>>;
+
+#[test]
+fn dry_run() -> AnyError {
+    let mut cmd = Command::cargo_bin(assert_cmd::pkg_name!())?;
+    let jobs = std::thread::available_parallelism()?;
+
+    cmd.args([
+        "--project", "./tests/cpp",
+        "--target", "test",
+        "--build-dir", "dry-run",
+        "--delete",
+        "--run",
+        "--dry-run",
+        "-DDEFINE1=v1",
+        "--env", "GREETING=hello there",
+        "--", "arg1",
+    ]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "CC=gcc CXX=g++ cmake -S ./tests/cpp -B ./tests/cpp/build/dry-run -DCMAKE_BUILD_TYPE=Debug"
+        ))
+        .stdout(predicate::str::contains("-DDEFINE1=v1 -DCFG1=cfg1 -DCFG2=cfg2"))
+        .stdout(predicate::str::contains(format!("cmake --build ./tests/cpp/build/dry-run --target test -- -j {jobs}\n")))
+        .stdout(predicate::str::contains("compile_commands.json ./tests/cpp/compile_commands.json"))
+        .stdout(predicate::str::contains("GREETING='hello there' "))
+        .stdout(predicate::str::contains("/tests/cpp/build/dry-run/test arg1"))
+    ;
+
+    assert!(!Path::new("./tests/cpp/build/dry-run").exists());
+
+    Ok(())
+}
+
+#[test]
+fn dry_run_coverage() -> AnyError {
+    let mut cmd = Command::cargo_bin(assert_cmd::pkg_name!())?;
+
+    cmd.args([
+        "--project", "./tests/cpp",
+        "--target", "test",
+        "--coverage",
+        "--run",
+        "--dry-run",
+    ]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("-B ./tests/cpp/build/debug-g++-coverage"))
+        .stdout(predicate::str::contains("-DCMAKE_CXX_FLAGS=--coverage "))
+        .stdout(predicate::str::contains("find ./tests/cpp/build/debug-g++-coverage -name '*.gcda' -delete"))
+        .stdout(predicate::str::contains("/tests/cpp/build/debug-g++-coverage/coverage/coverage.info"))
+    ;
+
+    assert!(!Path::new("./tests/cpp/build/debug-g++-coverage").exists());
+
+    Ok(())
+}
+
+#[test]
+fn dry_run_coverage_user_flags() -> AnyError {
+    let mut cmd = Command::cargo_bin(assert_cmd::pkg_name!())?;
+
+    cmd.args([
+        "--project", "./tests/cpp",
+        "--coverage",
+        "--dry-run",
+        "-DCMAKE_CXX_FLAGS=-Wall",
+    ]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("'-DCMAKE_CXX_FLAGS=-Wall --coverage'"))
+        .stdout(predicate::str::contains("-DCMAKE_C_FLAGS=--coverage "))
+    ;
+
+    Ok(())
+}
+
+#[test]
+fn dry_run_project_lookup() -> AnyError {
+    let mut cmd = Command::cargo_bin(assert_cmd::pkg_name!())?;
+    let project = fs::canonicalize("./tests/cpp")?;
+
+    cmd.current_dir(&project);
+    cmd.args(["--target", "test", "--run", "--dry-run"]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(format!("CC=gcc CXX=g++ cmake -S {}", project.display())))
+        .stdout(predicate::str::contains("-DCFG1=cfg1 -DCFG2=cfg2"))
+    ;
+
+    Ok(())
+}
+
+#[test]
+fn dry_run_init() -> AnyError {
+    let mut cmd = Command::cargo_bin(assert_cmd::pkg_name!())?;
+
+    cmd.args(["--project", "./tests/cpp", "init", "--dry-run"]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("cat > ./tests/cpp/.baldr.yaml <<'EOF'"))
+        .stdout(predicate::str::contains("# Configuration of baldr, generated by `baldr init`."))
+    ;
+
+    assert!(fs::read_to_string("./tests/cpp/.baldr.yaml")?.starts_with("debugger: gdb"));
+
+    Ok(())
+}
+
+#[test]
+fn dry_run_jobs() -> AnyError {
+    let mut cmd = Command::cargo_bin(assert_cmd::pkg_name!())?;
+
+    cmd.args(["--project", "./tests/cpp", "--dry-run", "-j", "300", "-l", "4.5"]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("-- -j 300 -l 4.5\n"))
+    ;
+
+    Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["--project", "./tests/cpp", "--build-dir", "dry-run-jobs", "--dry-run", "-j", "300", "-l", "4.5"])
+        .env("CMAKE_GENERATOR", "Xcode")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("-- -j 300\n"))
+    ;
+
+    Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["--project", "./tests/cpp", "--dry-run", "--load-average=-1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid load average `-1`"))
+    ;
+
+    Ok(())
+}
+
+#[test]
+fn dry_run_named_run() -> AnyError {
+    let file = std::env::temp_dir().join(format!("baldr-runs-{}.yaml", std::process::id()));
+    fs::write(&file, "run:\n  limits:\n    cpu: 60\n    files: 256\nruns:\n  leaks:\n    target: test\n    args: --verbose\n    wrapper: valgrind -q\n    env:\n      - MODE=leaks\n    timeout: 10s\n    limits:\n      files: 128\n")?;
+    let mut cmd = Command::cargo_bin(assert_cmd::pkg_name!())?;
+
+    cmd.args(["--project", "./tests/cpp", "--build-dir", "dry-run-runs", "--dry-run", "--config"])
+        .arg(&file)
+        .args(["run", "leaks", "--", "extra"]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("cmake --build ./tests/cpp/build/dry-run-runs --target test"))
+        .stdout(predicate::str::contains("# Timeout: 10s"))
+        .stdout(predicate::str::contains("# Limits: cpu: 60s, files: 128"))
+        .stdout(predicate::str::is_match(r"MODE=leaks valgrind -q /.*/tests/cpp/build/dry-run-runs/test --verbose extra\n")?)
+    ;
+
+    let mut cmd = Command::cargo_bin(assert_cmd::pkg_name!())?;
+
+    cmd.args(["--project", "./tests/cpp", "--dry-run", "--config"])
+        .arg(&file)
+        .args(["run", "server"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No run configuration `server`, available ones: leaks"))
+    ;
+
+    fs::remove_file(file)?;
+    Ok(())
+}
+
+#[test]
+fn dry_run_cache_set() -> AnyError {
+    let mut cmd = Command::cargo_bin(assert_cmd::pkg_name!())?;
+
+    cmd.args(["--project", "./tests/cpp", "--build-dir", "dry-run-cache", "--dry-run", "cache", "set", "OPTION1", "ON", "--type", "BOOL"]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(" -DOPTION1:BOOL=ON "))
+        .stdout(predicate::str::contains("cmake --build").not())
+    ;
+
+    Ok(())
+}
+
+#[test]
+fn dry_run_workspace() -> AnyError {
+    let root = std::env::temp_dir().join(format!("baldr-workspace-{}", std::process::id()));
+
+    for member in ["libs/core", "apps/app"] {
+        fs::create_dir_all(root.join(member))?;
+        fs::write(root.join(member).join("CMakeLists.txt"), "project(member)\n")?;
+    }
+    fs::write(
+        root.join(".baldr.yaml"),
+        "workspace:\n  members:\n    app:\n      path: apps/app\n      depends: [core]\n    core:\n      path: libs/core\n",
+    )?;
+    fs::write(root.join("apps/app/.baldr.yaml"), "cmake:\n  definitions:\n    - CMAKE_PREFIX_PATH=/opt/qt\n")?;
+
+    let staging = root.join("build/debug/staging");
+    let mut cmd = Command::cargo_bin(assert_cmd::pkg_name!())?;
+
+    cmd.arg("--project").arg(&root).args(["--dry-run", "workspace", "build"]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::is_match(format!(
+            r"(?s)# Workspace member: core\n.*-B {0}/libs/core/build/debug [^\n]*-DCMAKE_EXPORT_COMPILE_COMMANDS=ON\n.*cmake --install {0}/libs/core/build/debug --config Debug --prefix {1}\n# Workspace member: app\n.*'-DCMAKE_PREFIX_PATH={1};/opt/qt'\n.*cmake --install {0}/apps/app/build/debug --config Debug --prefix {1}\n$",
+            root.display(),
+            staging.display(),
+        ))?)
+    ;
+
+    let mut cmd = Command::cargo_bin(assert_cmd::pkg_name!())?;
+    cmd.arg("--project").arg(&root).args(["--build-dir", "/tmp/build", "--dry-run", "workspace", "build"]);
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Absolute build directory `/tmp/build` would be shared by the workspace members"))
+    ;
+
+    fs::remove_dir_all(root)?;
+    Ok(())
+}
@@ -6,7 +6,7 @@ use std::process::Command;
 type AnyError = Result<(), Box<dyn std::error::Error>>;
 
 fn command() -> Command {
-    let mut cmd = Command::cargo_bin(assert_cmd::crate_name!()).unwrap();
+    let mut cmd = Command::cargo_bin(assert_cmd::pkg_name!()).expect("Binary is not built");
 
     cmd.args([
         "--project", "./tests/cpp",
@@ -0,0 +1,47 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+
+type AnyError = Result<(), Box<dyn std::error::Error>>;
+
+fn config(name: &str, content: &str) -> Result<PathBuf, std::io::Error> {
+    let file = std::env::temp_dir().join(format!("baldr-hooks-{name}-{}.yaml", std::process::id()));
+    fs::write(&file, content)?;
+    Ok(file)
+}
+
+#[test]
+fn failing_pre_hook() -> AnyError {
+    let file = config("failing", "hooks:\n  pre_configure: exit 3\n")?;
+    let mut cmd = Command::cargo_bin(assert_cmd::pkg_name!())?;
+
+    cmd.args(["--project", "./tests/cpp", "--build-dir", "hooks-test", "--config"])
+        .arg(&file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Hook `pre_configure` (`exit 3`) has failed"))
+    ;
+
+    fs::remove_file(file)?;
+    Ok(())
+}
+
+#[test]
+fn dry_run_hooks() -> AnyError {
+    let file = config("dry-run", "hooks:\n  pre_build: echo pre\n  post_run:\n    - echo one\n    - echo two\n")?;
+    let mut cmd = Command::cargo_bin(assert_cmd::pkg_name!())?;
+
+    cmd.args(["--project", "./tests/cpp", "--target", "test", "--build-dir", "hooks-test", "--run", "--dry-run", "--config"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"BALDR_BUILD_TYPE=Debug .*BALDR_EXE=/.*/tests/cpp/build/hooks-test/test .*sh -c 'echo pre'\ncmake --build")?)
+        .stdout(predicate::str::is_match(r"BALDR_TARGET=test sh -c 'echo one'\n.*sh -c 'echo two'\n$")?)
+    ;
+
+    fs::remove_file(file)?;
+    Ok(())
+}
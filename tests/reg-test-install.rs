@@ -0,0 +1,49 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+
+type AnyError = Result<(), Box<dyn std::error::Error>>;
+
+const BUILD_DIR: &str = "./tests/cpp/build/install-test";
+
+fn command() -> Command {
+    let mut cmd = Command::cargo_bin(assert_cmd::pkg_name!()).expect("Binary is not built");
+    cmd.args(["--project", "./tests/cpp", "--build-dir", "install-test"]);
+    cmd
+}
+
+#[test]
+fn install() -> AnyError {
+    command()
+        .args(["--target", "test", "--delete", "--no-confirm"])
+        .assert()
+        .success()
+    ;
+
+    let prefix = Path::new(BUILD_DIR).join("stage");
+    command()
+        .args(["install", "--prefix", &prefix.to_string_lossy()])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Installed files:"))
+        .stdout(predicate::str::contains("stage/bin/test"))
+    ;
+
+    assert!(prefix.join("bin").join("test").exists());
+
+    fs::remove_dir_all(BUILD_DIR)?;
+    Ok(())
+}
+
+#[test]
+fn install_not_built() {
+    Command::cargo_bin(assert_cmd::pkg_name!()).expect("Binary is not built")
+        .args(["--project", "./tests/cpp", "--build-dir", "never-built", "install", "--prefix", "/tmp/baldr"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("is not configured"))
+    ;
+}
@@ -0,0 +1,42 @@
+use std::fs;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use assert_cmd::prelude::*;
+
+type AnyError = Result<(), Box<dyn std::error::Error>>;
+
+#[test]
+fn interrupted_hook() -> AnyError {
+    let dir = std::env::temp_dir().join(format!("baldr-signals-{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+
+    let marker = dir.join("started");
+    let file = dir.join("config.yaml");
+    fs::write(&file, format!("hooks:\n  pre_configure: touch {} && sleep 30\n", marker.display()))?;
+
+    let start = Instant::now();
+    let mut child = Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["--project", "./tests/cpp", "--build-dir", "signals-test", "--config"])
+        .arg(&file)
+        .process_group(0)
+        .spawn()?;
+
+    while !marker.exists() {
+        assert!(start.elapsed() < Duration::from_secs(10), "Hook has not started");
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    // Like `Ctrl-C` of the terminal, the hook is in the foreground process group of baldr.
+    // SAFETY: plain syscall, the child is not reaped yet.
+    assert_eq!(unsafe { libc::kill(-libc::pid_t::try_from(child.id())?, libc::SIGINT) }, 0);
+
+    let status = child.wait()?;
+    assert_eq!(status.code(), Some(128 + libc::SIGINT));
+    assert!(start.elapsed() < Duration::from_secs(10), "Hook has not been interrupted");
+
+    fs::remove_dir_all(dir)?;
+    Ok(())
+}
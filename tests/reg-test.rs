@@ -1,4 +1,5 @@
-use std::process::Command;
+use std::path::Path;
+use std::process::{Command, Stdio};
 
 use assert_cmd::prelude::*;
 use predicates::prelude::*;
@@ -7,7 +8,7 @@ type AnyError = Result<(), Box<dyn std::error::Error>>;
 
 #[test]
 fn build() -> AnyError {
-    let mut cmd = Command::cargo_bin(assert_cmd::crate_name!())?;
+    let mut cmd = Command::cargo_bin(assert_cmd::pkg_name!())?;
 
     // TODO(refact): pull out the common part of the command to a function.
     cmd.args([
@@ -24,7 +25,7 @@ fn build() -> AnyError {
 
 #[test]
 fn build_and_run() -> AnyError {
-    let mut cmd = Command::cargo_bin(assert_cmd::crate_name!())?;
+    let mut cmd = Command::cargo_bin(assert_cmd::pkg_name!())?;
 
     cmd.args([
         "--project", "./tests/cpp",
@@ -45,7 +46,7 @@ fn build_and_run() -> AnyError {
 
 #[test]
 fn arguments() -> AnyError {
-    let mut cmd = Command::cargo_bin(assert_cmd::crate_name!())?;
+    let mut cmd = Command::cargo_bin(assert_cmd::pkg_name!())?;
 
     cmd.args([
         "--project", "./tests/cpp",
@@ -66,7 +67,7 @@ fn arguments() -> AnyError {
 
 #[test]
 fn cmake_definitions() -> AnyError {
-    let mut cmd = Command::cargo_bin(assert_cmd::crate_name!())?;
+    let mut cmd = Command::cargo_bin(assert_cmd::pkg_name!())?;
 
     cmd.args([
         "--project", "./tests/cpp",
@@ -88,7 +89,7 @@ fn cmake_definitions() -> AnyError {
 
 #[test]
 fn cmake_definitions_2() -> AnyError {
-    let mut cmd = Command::cargo_bin(assert_cmd::crate_name!())?;
+    let mut cmd = Command::cargo_bin(assert_cmd::pkg_name!())?;
 
     cmd.args([
         "--project", "./tests/cpp",
@@ -110,7 +111,7 @@ fn cmake_definitions_2() -> AnyError {
 
 #[test]
 fn cmake_definitions_3_no_configure() -> AnyError {
-    let mut cmd1 = Command::cargo_bin(assert_cmd::crate_name!())?;
+    let mut cmd1 = Command::cargo_bin(assert_cmd::pkg_name!())?;
 
     cmd1.args([
         "--project", "./tests/cpp",
@@ -123,7 +124,7 @@ fn cmake_definitions_3_no_configure() -> AnyError {
 
     cmd1.assert().success();
 
-    let mut cmd2 = Command::cargo_bin(assert_cmd::crate_name!())?;
+    let mut cmd2 = Command::cargo_bin(assert_cmd::pkg_name!())?;
 
     cmd2.args([
         "--project", "./tests/cpp",
@@ -132,11 +133,283 @@ fn cmake_definitions_3_no_configure() -> AnyError {
         "--run",
         "--no-configure",
     ]);
+    cmd2.env("RUST_LOG", "warn");
 
     cmd2.assert()
         .success()
         .stdout(predicate::str::contains("Defines: v17"))
+        .stderr(predicate::str::contains("Not configuring due to `--no-configure`, although definitions have changed: +DEFINE1=v4, -DEFINE1=v17."))
     ;
 
     Ok(())
 }
+
+#[test]
+fn bench() -> AnyError {
+    let mut cmd = Command::cargo_bin(assert_cmd::pkg_name!())?;
+
+    cmd.args([
+        "--project", "./tests/cpp",
+        "--target", "test",
+        "--build-dir", "bench-test",
+        "--bench", "3",
+        "--delete",
+        "--no-confirm",
+    ]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Arguments:").not())
+        .stdout(predicate::str::is_match("wall time +[0-9.]+[µm]?s")?)
+        .stdout(predicate::str::contains("peak RSS"))
+    ;
+
+    let history = std::fs::read_to_string("./tests/cpp/build/bench-test/bench/test.jsonl")?;
+    assert!(history.contains("\"runs\":3"));
+
+    std::fs::remove_dir_all("./tests/cpp/build/bench-test")?;
+
+    Ok(())
+}
+
+#[test]
+fn logs() -> AnyError {
+    let args = ["--project", "./tests/cpp", "--target", "test", "--build-dir", "logs-test"];
+    let config = std::env::temp_dir().join(format!("baldr-logs-{}.yaml", std::process::id()));
+    std::fs::write(&config, "log:\n  run: true\n")?;
+
+    Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(args)
+        .args(["--run", "--delete", "--no-confirm", "--config"])
+        .arg(&config)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Arguments:"))
+    ;
+
+    Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(args)
+        .arg("log")
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"^\d{8}-\d{6}\.\d{3}-configure\.log  success\n.*-build\.log  success\n.*-run\.log  success\n$")?)
+    ;
+
+    Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(args)
+        .args(["log", "--last"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"\[\d{2}:\d{2}:\d{2}\.\d{3}\] Arguments:")?)
+        .stdout(predicate::str::ends_with("# Result: success\n"))
+    ;
+
+    Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(args)
+        .args(["log", "--failed"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No failed invocation"))
+    ;
+
+    std::fs::remove_dir_all("./tests/cpp/build/logs-test")?;
+    std::fs::remove_file(config)?;
+    Ok(())
+}
+
+#[test]
+fn reconfigure_on_change() -> AnyError {
+    let args = ["--project", "./tests/cpp", "--target", "test", "--build-dir", "reconfigure-test"];
+
+    Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(args)
+        .args(["--delete", "--no-confirm"])
+        .env("RUST_LOG", "info")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Configuring, as the build directory is new."))
+    ;
+
+    Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(args)
+        .env("RUST_LOG", "info")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Configure inputs are unchanged, skipping configuring."))
+    ;
+
+    Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(args)
+        .args(["-DDEFINE1=v5", "--run"])
+        .env("RUST_LOG", "info")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Configuring, as definitions have changed: +DEFINE1=v5."))
+        .stdout(predicate::str::contains("Defines: v5"))
+    ;
+
+    std::fs::remove_dir_all("./tests/cpp/build/reconfigure-test")?;
+    Ok(())
+}
+
+#[test]
+fn reconfigure_after_dependencies() -> AnyError {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = std::env::temp_dir().join(format!("baldr-conan-{}", std::process::id()));
+    let project = dir.join("project");
+    let bin = dir.join("bin");
+    std::fs::create_dir_all(&project)?;
+    std::fs::create_dir_all(&bin)?;
+
+    for file in ["CMakeLists.txt", "main.cc"] {
+        std::fs::copy(Path::new("./tests/cpp").join(file), project.join(file))?;
+    }
+    std::fs::write(project.join("conanfile.txt"), "[requires]\n")?;
+    std::fs::write(project.join(".baldr.yaml"), "dependencies:\n  manager: conan\n")?;
+
+    // Generates the toolchain where `cmake_layout` puts it.
+    let conan = bin.join("conan");
+    std::fs::write(&conan, "#!/bin/sh\nwhile [ $# -gt 0 ]; do [ \"$1\" = --output-folder ] && out=$2; shift; done\nmkdir -p \"$out/build/Debug/generators\" && touch \"$out/build/Debug/generators/conan_toolchain.cmake\"\n")?;
+    std::fs::set_permissions(&conan, std::fs::Permissions::from_mode(0o755))?;
+
+    let path = std::env::join_paths(std::iter::once(bin).chain(std::env::split_paths(&std::env::var_os("PATH").unwrap_or_default())))?;
+
+    Command::cargo_bin(assert_cmd::pkg_name!())?
+        .arg("--project").arg(&project)
+        .env("PATH", &path)
+        .env("RUST_LOG", "info")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Configuring, as the build directory is new."))
+    ;
+
+    Command::cargo_bin(assert_cmd::pkg_name!())?
+        .arg("--project").arg(&project)
+        .env("PATH", &path)
+        .env("RUST_LOG", "info")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Configure inputs are unchanged, skipping configuring."))
+    ;
+
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn cache_mismatch() -> AnyError {
+    let args = ["--project", "./tests/cpp", "--target", "test", "--build-dir", "mismatch-test"];
+
+    Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(args)
+        .args(["--delete", "--no-confirm"])
+        .assert()
+        .success()
+    ;
+
+    let cache = "./tests/cpp/build/mismatch-test/CMakeCache.txt";
+    let moved = std::fs::read_to_string(cache)?
+        .lines()
+        .map(|x| if x.starts_with("CMAKE_HOME_DIRECTORY:") { "CMAKE_HOME_DIRECTORY:INTERNAL=/moved/project" } else { x })
+        .collect::<Vec<_>>()
+        .join("\n");
+    std::fs::write(cache, moved)?;
+
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(args)
+        .write_stdin("n\n")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("`CMAKE_HOME_DIRECTORY` is `/moved/project`, but"))
+        .stderr(predicate::str::contains("cannot be reconfigured in place"))
+    ;
+
+    // Without an answer (e.g. in CI) the build directory is kept.
+    Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(args)
+        .stdin(Stdio::null())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be reconfigured in place"))
+        .stderr(predicate::str::contains("panicked").not())
+    ;
+
+    Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(args)
+        .arg("--no-confirm")
+        .env("RUST_LOG", "warn")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Build directory is configured with different settings (`CMAKE_HOME_DIRECTORY` is `/moved/project`"))
+    ;
+
+    assert!(!std::fs::read_to_string(cache)?.contains("/moved/project"));
+
+    std::fs::remove_dir_all("./tests/cpp/build/mismatch-test")?;
+    Ok(())
+}
+
+#[test]
+fn cache_edit() -> AnyError {
+    let args = ["--project", "./tests/cpp", "--target", "test", "--build-dir", "cache-test"];
+
+    Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(args)
+        .args(["--delete", "--no-confirm"])
+        .assert()
+        .success()
+    ;
+
+    Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(args)
+        .args(["cache", "set", "DEFINE1", "v9"])
+        .assert()
+        .success()
+    ;
+
+    Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(args)
+        .args(["cache", "get", "DEFINE1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::ends_with("DEFINE1:STRING=v9\n"))
+    ;
+
+    Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(args)
+        .args(["cache", "list", "--filter", "define"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("DEFINE1:STRING=v9\n"))
+        .stdout(predicate::str::contains("CMAKE_BUILD_TYPE").not())
+    ;
+
+    Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(args)
+        .args(["--run"])
+        .env("RUST_LOG", "info")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Configure inputs are unchanged"))
+        .stdout(predicate::str::contains("Defines: v9"))
+    ;
+
+    Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(args)
+        .args(["cache", "unset", "DEFINE1"])
+        .assert()
+        .success()
+    ;
+
+    Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(args)
+        .args(["cache", "get", "DEFINE1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("`DEFINE1` is not in the cache"))
+    ;
+
+    std::fs::remove_dir_all("./tests/cpp/build/cache-test")?;
+    Ok(())
+}